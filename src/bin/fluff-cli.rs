@@ -0,0 +1,18 @@
+use std::{env, process};
+
+use fluff::cli::{self, Command};
+
+// the command line tools, without the window
+fn main() {
+    let args: Vec<_> = env::args().collect();
+    let (command, options) = match cli::parse(&args) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, cli::USAGE);
+            process::exit(2);
+        }
+    };
+    // the window lives in the fluff binary
+    let command = if command == Command::Run { Command::Help } else { command };
+    process::exit(cli::run(&command, &options));
+}
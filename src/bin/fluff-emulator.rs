@@ -0,0 +1,79 @@
+use std::io::{self, BufRead, BufReader};
+use std::time::Duration;
+use std::{env, process};
+
+use fluff::board::Board;
+use fluff::cli;
+use fluff::matrix::PatchMatrix;
+use fluff::protocol;
+
+const USAGE: &str = "usage: fluff-emulator [--port <name>] [--baud <rate>] [--board <file>]";
+
+// stands in for the board, reading the serial protocol from a port or stdin and printing
+// the connections it makes, to try the controller without the hardware
+fn main() {
+    let args: Vec<_> = env::args().collect();
+    let options = match cli::parse(&args) {
+        Ok((_, options)) => options,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+    let board = options.load_board().unwrap_or_else(|e| {
+        eprintln!("Failed to load board: {}", e);
+        Board::spectre()
+    });
+    let mut matrix = PatchMatrix::new(board);
+
+    let mut input: Box<dyn BufRead> = match &options.port {
+        Some(port_name) => match serialport::new(port_name, options.baudrate)
+            .timeout(Duration::from_secs(1))
+            .open()
+        {
+            Ok(port) => {
+                eprintln!("emulating on {} at {}", port_name, options.baudrate);
+                Box::new(BufReader::new(port))
+            }
+            Err(e) => {
+                eprintln!("Failed to open port: {}", e);
+                process::exit(2);
+            }
+        },
+        None => Box::new(BufReader::new(io::stdin())),
+    };
+
+    let mut line = String::new();
+    loop {
+        line.clear();
+        match input.read_line(&mut line) {
+            Ok(0) => return,
+            Ok(_) => receive(&mut matrix, &line),
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => (),
+            Err(e) => {
+                eprintln!("Failed to read: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+}
+
+fn receive(matrix: &mut PatchMatrix, line: &str) {
+    let (row, column, state) = match protocol::parse_line(line) {
+        Some(parsed) if parsed.0 < matrix.rows() && parsed.1 < matrix.columns() => parsed,
+        _ => {
+            println!("ignored {:?}", line.trim_end());
+            return;
+        }
+    };
+    let value = if state == '0' { 0.0 } else { 1.0 };
+    if matrix.set(row, column, value) {
+        let board = matrix.board();
+        println!(
+            "{} -> {} {}",
+            board.sources[column].name,
+            board.destinations[row].name,
+            if value != 0.0 { "on" } else { "off" }
+        );
+    }
+}
@@ -0,0 +1,185 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+// the labels of a matrix board, sources feed the serial columns and destinations the serial rows
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Board {
+    pub name:         String,
+    pub sources:      Vec<Label>,
+    pub destinations: Vec<Label>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Label {
+    pub name:        String,
+    #[serde(default)]
+    pub description: String,
+}
+
+impl Board {
+    // the open spectre prototype, without descriptions
+    pub fn spectre() -> Board {
+        let labels = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| Label { name: name.to_string(), description: String::new() })
+                .collect()
+        };
+        Board {
+            name:         "spectre".to_string(),
+            sources:      labels(&SPECTRE_SOURCES),
+            destinations: labels(&SPECTRE_DESTINATIONS),
+        }
+    }
+
+    pub fn load(path: &Path) -> io::Result<Board> {
+        let file = fs::read_to_string(path)?;
+        serde_json::from_str(&file).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let file = serde_json::to_string_pretty(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(path, file)
+    }
+
+    // (errors, warnings), errors make the board unusable, warnings are printed but pass
+    pub fn check(&self) -> (Vec<String>, Vec<String>) {
+        let mut errors = vec![];
+        let mut warnings = vec![];
+
+        for (kind, labels) in [("source", &self.sources), ("destination", &self.destinations)] {
+            if labels.is_empty() {
+                errors.push(format!("no {}s", kind));
+            }
+            // the serial protocol has two digits per index
+            if labels.len() > 100 {
+                errors.push(format!("{} {}s, at most 100 fit the protocol", labels.len(), kind));
+            }
+            for (i, label) in labels.iter().enumerate() {
+                if label.name.trim().is_empty() {
+                    errors.push(format!("{} {} has no name", kind, i));
+                } else if labels[..i].iter().any(|other| other.name == label.name) {
+                    warnings.push(format!("{} {} repeats the name {}", kind, i, label.name));
+                }
+            }
+        }
+        (errors, warnings)
+    }
+}
+
+const SPECTRE_SOURCES: [&str; 50] = [
+    "counter_x_0",
+    "counter_x_1",
+    "counter_x_2",
+    "counter_x_3",
+    "counter_x_4",
+    "counter_x_5",
+    "counter_x_6",
+    "counter_x_7",
+    "counter_x_8",
+    "counter_y_0",
+    "counter_y_1",
+    "counter_y_2",
+    "counter_y_3",
+    "counter_y_4",
+    "counter_y_5",
+    "counter_y_6",
+    "counter_y_7",
+    "counter_y_8",
+    "slow_count_6",
+    "slow_count_3",
+    "slow_count_1.5",
+    "slow_count_.8",
+    "slow_count_.4",
+    "slow_count_.2",
+    "overlay_1",
+    "overlay_2",
+    "overlay_3",
+    "overlay_4",
+    "invert_a",
+    "invert_b",
+    "invert_c",
+    "invert_d",
+    "edge_thin_+",
+    "edge_thin_-",
+    "edge_wide_+",
+    "edge_wide_-",
+    "delay",
+    "flip_flop_+",
+    "flip_flop_-",
+    "n/a",
+    "n/a",
+    "n/a",
+    "n/a",
+    "comp_0",
+    "comp_1",
+    "comp_2",
+    "comp_3",
+    "comp_4",
+    "comp_5",
+    "comp_6",
+];
+
+const SPECTRE_DESTINATIONS: [&str; 57] = [
+    "invert_x_0",
+    "invert_x_1",
+    "invert_x_2",
+    "invert_x_3",
+    "invert_x_4",
+    "invert_x_5",
+    "invert_x_6",
+    "invert_x_7",
+    "invert_x_8",
+    "invert_y_0",
+    "invert_y_1",
+    "invert_y_2",
+    "invert_y_3",
+    "invert_y_4",
+    "invert_y_5",
+    "invert_y_6",
+    "invert_y_7",
+    "invert_y_8",
+    "overlay_gate_1_dis",
+    "overlay_gate_1_sig",
+    "overlay_gate_2_dis",
+    "overlay_gate_2_sig",
+    "overlay_gate_3_dis",
+    "overlay_gate_3_sig",
+    "overlay_gate_4_dis",
+    "overlay_gate_4_sig",
+    "invert_a",
+    "invert_b",
+    "invert_c",
+    "invert_d",
+    "edge",
+    "delay",
+    "flip_flop_+",
+    "flip_flop_-",
+    "to_acm_fast",
+    "to_acm_slow",
+    "out_a_luma_0",
+    "out_a_luma_1",
+    "out_a_luma_2",
+    "out_a_luma_3",
+    "out_a_col1_0",
+    "out_a_col1_1",
+    "out_a_col1_2",
+    "out_a_col2_0",
+    "out_a_col2_1",
+    "out_a_col2_2",
+    "out_b_luma_0",
+    "out_b_luma_1",
+    "out_b_luma_2",
+    "out_b_luma_3",
+    "out_b_col1_0",
+    "out_b_col1_1",
+    "out_b_col1_2",
+    "out_b_col2_0",
+    "out_b_col2_1",
+    "out_b_col2_2",
+    "col_swap",
+];
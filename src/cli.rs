@@ -0,0 +1,416 @@
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serialport::SerialPortType;
+
+use crate::board::Board;
+use crate::{headless, tui};
+use crate::patch::{Lock, Patch};
+use crate::port_scanner::PortMatcher;
+use crate::protocol;
+use crate::sheet;
+use crate::transport::{self, Transport};
+use crate::BAUDRATE;
+
+pub const PATCH_DEFAULT: &str = "patch.json";
+// used instead of the built in spectre labels when present
+pub const BOARD_DEFAULT: &str = "board.json";
+pub const SCREENSHOTS_DEFAULT: &str = "screenshots";
+
+pub const USAGE: &str = "usage: fluff [command] [options]
+
+commands:
+    run                 open the patch matrix window (default)
+    list-ports          list the serial ports with their usb ids
+    send <patch>        send a patch file to the board and exit
+    monitor             print what the board sends back
+    diff <a> <b>        show the differences between two patch files
+    validate <board>    check a board definition file
+    sheet <patch> [svg] print a patch sheet, next to the patch file by default
+    headless            run the patch state without a window, driven by text commands
+    tui                 patch the matrix from the terminal
+
+options:
+    -p, --port <name>           serial port, otherwise the first usb serial device
+    -b, --baud <rate>           baud rate, 115200 by default
+    --usb-id <vid:pid>          only pick a port with this usb id, in hex
+    --board <file>              board definition, board.json or the spectre board by default
+    --patch <file>              patch file loaded on start and saved to, patch.json by default
+    -t, --transport <kind>      serial or stdout
+    --listen <address>          headless: also take commands over tcp, like 0.0.0.0:7000
+    --screenshots <folder>      where the window saves screenshots, screenshots by default
+    -h, --help                  show this message
+
+the old form `fluff <port> [baud]` still works for ports like /dev/ttyACM0 or COM3";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Run,
+    ListPorts,
+    Send(PathBuf),
+    Monitor,
+    Diff(PathBuf, PathBuf),
+    Validate(PathBuf),
+    Sheet(PathBuf, Option<PathBuf>),
+    Headless,
+    Tui,
+    Help,
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub port:        Option<String>,
+    pub baudrate:    u32,
+    pub usb_id:      Option<(u16, u16)>,
+    pub board:       Option<PathBuf>,
+    pub patch:       Option<PathBuf>,
+    pub transport:   Transport,
+    pub listen:      Option<String>,
+    pub screenshots: Option<PathBuf>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            port:        None,
+            baudrate:    BAUDRATE,
+            usb_id:      None,
+            board:       None,
+            patch:       None,
+            transport:   Transport::Serial,
+            listen:      None,
+            screenshots: None,
+        }
+    }
+}
+
+impl Options {
+    // a port given by name is the only one picked, otherwise any usb serial device
+    pub fn port_matcher(&self) -> PortMatcher {
+        match &self.port {
+            Some(port) => PortMatcher { usb_id: self.usb_id, patterns: vec![port.clone()] },
+            None => PortMatcher::usb_serial(self.usb_id),
+        }
+    }
+
+    pub fn patch_path(&self) -> PathBuf {
+        self.patch.clone().unwrap_or_else(|| PathBuf::from(PATCH_DEFAULT))
+    }
+
+    pub fn screenshot_dir(&self) -> PathBuf {
+        self.screenshots.clone().unwrap_or_else(|| PathBuf::from(SCREENSHOTS_DEFAULT))
+    }
+
+    // a board that fails its check is an error, an empty one can't be drawn or addressed
+    pub fn load_board(&self) -> io::Result<Board> {
+        let board = match &self.board {
+            Some(path) => Board::load(path)?,
+            None if Path::new(BOARD_DEFAULT).exists() => Board::load(Path::new(BOARD_DEFAULT))?,
+            None => return Ok(Board::spectre()),
+        };
+        let (errors, warnings) = board.check();
+        for warning in &warnings {
+            eprintln!("warning: {}", warning);
+        }
+        if !errors.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, errors.join(", ")));
+        }
+        Ok(board)
+    }
+}
+
+pub fn parse(args: &[String]) -> Result<(Command, Options), String> {
+    let mut options = Options::default();
+    let mut positional = vec![];
+
+    let mut args = args.iter().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| args.next().cloned().ok_or(format!("{} needs a value", flag));
+        match arg.as_str() {
+            "-p" | "--port" => options.port = Some(value(arg)?),
+            "-b" | "--baud" => options.baudrate = parse_baudrate(&value(arg)?)?,
+            "--usb-id" => options.usb_id = Some(parse_usb_id(&value(arg)?)?),
+            "--board" => options.board = Some(PathBuf::from(value(arg)?)),
+            "--patch" => options.patch = Some(PathBuf::from(value(arg)?)),
+            "-t" | "--transport" => options.transport = value(arg)?.parse()?,
+            "--listen" => options.listen = Some(value(arg)?),
+            "--screenshots" => options.screenshots = Some(PathBuf::from(value(arg)?)),
+            "-h" | "--help" => return Ok((Command::Help, options)),
+            flag if flag.starts_with('-') => return Err(format!("unknown option {}", flag)),
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    let path = |i: usize, name: &str| -> Result<PathBuf, String> {
+        positional.get(i).map(PathBuf::from).ok_or(format!("missing {}", name))
+    };
+    let command = match positional.first().map(|arg| arg.as_str()) {
+        Some("list-ports") => Command::ListPorts,
+        Some("send") => Command::Send(path(1, "patch file")?),
+        Some("monitor") => Command::Monitor,
+        Some("diff") => Command::Diff(path(1, "first patch file")?, path(2, "second patch file")?),
+        Some("validate") => Command::Validate(path(1, "board file")?),
+        Some("sheet") => Command::Sheet(path(1, "patch file")?, path(2, "svg file").ok()),
+        Some("headless") => Command::Headless,
+        Some("tui") => Command::Tui,
+        Some("run") | None => {
+            legacy_port(&positional[positional.len().min(1)..], &mut options)?;
+            Command::Run
+        }
+        Some(port) if looks_like_port(port) => {
+            legacy_port(&positional, &mut options)?;
+            Command::Run
+        }
+        Some(other) => return Err(format!("unknown command {}", other)),
+    };
+    Ok((command, options))
+}
+
+// `fluff <port> [baud]`
+fn legacy_port(args: &[String], options: &mut Options) -> Result<(), String> {
+    if let Some(port) = args.first() {
+        options.port = Some(port.clone());
+    }
+    if let Some(baudrate) = args.get(1) {
+        options.baudrate = parse_baudrate(baudrate)?;
+    }
+    Ok(())
+}
+
+// a device path like `/dev/ttyACM0` or a bare name like `COM3` or `ttyUSB0`
+fn looks_like_port(arg: &str) -> bool {
+    arg.contains('/')
+        || arg.contains('\\')
+        || ["COM", "tty", "cu."].iter().any(|prefix| arg.starts_with(prefix))
+}
+
+fn parse_baudrate(arg: &str) -> Result<u32, String> {
+    arg.parse().map_err(|_| format!("baud rate must be an unsigned integer, got {}", arg))
+}
+
+fn parse_usb_id(arg: &str) -> Result<(u16, u16), String> {
+    let error = || format!("usb id must look like 16c0:0483, got {}", arg);
+    let (vid, pid) = arg.split_once(':').ok_or_else(error)?;
+    let hex = |id: &str| u16::from_str_radix(id, 16).map_err(|_| error());
+    Ok((hex(vid)?, hex(pid)?))
+}
+
+// runs a command that doesn't need the window, returns the exit code
+pub fn run(command: &Command, options: &Options) -> i32 {
+    let result = match command {
+        Command::Run => return 0,
+        Command::Help => {
+            println!("{}", USAGE);
+            Ok(true)
+        }
+        Command::ListPorts => list_ports(),
+        Command::Send(path) => send(path, options),
+        Command::Monitor => monitor(options),
+        Command::Diff(a, b) => diff(a, b, options),
+        Command::Validate(path) => validate(path),
+        Command::Sheet(path, output) => write_sheet(path, output.as_deref(), options),
+        Command::Headless => headless::run(options),
+        Command::Tui => tui::run(options),
+    };
+    match result {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(e) => {
+            eprintln!("{}", e);
+            2
+        }
+    }
+}
+
+fn list_ports() -> io::Result<bool> {
+    let ports = serialport::available_ports().map_err(io::Error::from)?;
+    for port in ports {
+        match port.port_type {
+            SerialPortType::UsbPort(usb) => println!(
+                "{}\tusb {:04x}:{:04x}\t{}",
+                port.port_name,
+                usb.vid,
+                usb.pid,
+                usb.product.unwrap_or_default()
+            ),
+            _ => println!("{}", port.port_name),
+        }
+    }
+    Ok(true)
+}
+
+fn send(path: &Path, options: &Options) -> io::Result<bool> {
+    let patch = Patch::load(path)?;
+    let board = options.load_board()?;
+    let mut output =
+        options.transport.open(options.port.as_deref(), &options.port_matcher(), options.baudrate)?;
+    output.write_all(protocol::patch_lines(&patch, &board).as_bytes())?;
+    output.flush()?;
+    Ok(true)
+}
+
+fn monitor(options: &Options) -> io::Result<bool> {
+    let port_name = match &options.port {
+        Some(port) => port.clone(),
+        None => transport::find_port(&options.port_matcher())
+            .ok_or(io::Error::new(io::ErrorKind::NotFound, "no matching port found"))?,
+    };
+    let port = serialport::new(&port_name, options.baudrate)
+        .timeout(Duration::from_secs(1))
+        .open()
+        .map_err(io::Error::from)?;
+    eprintln!("monitoring {} at {}", port_name, options.baudrate);
+
+    let mut reader = BufReader::new(port);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        match reader.read_line(&mut line) {
+            Ok(0) => return Ok(true),
+            Ok(_) => print!("{}", line),
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => (),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// prints one line per changed cell or lock, false when the patches differ
+fn diff(a: &Path, b: &Path, options: &Options) -> io::Result<bool> {
+    let (a, b) = (Patch::load(a)?, Patch::load(b)?);
+    let board = options.load_board()?;
+    let name = |labels: &Vec<crate::board::Label>, i: usize| {
+        labels.get(i).map_or(format!("{:02}", i), |label| label.name.clone())
+    };
+
+    let mut cells: Vec<_> =
+        a.cells.iter().chain(b.cells.iter()).map(|cell| (cell.row, cell.column)).collect();
+    cells.sort();
+    cells.dedup();
+
+    let mut same = true;
+    for (row, column) in cells {
+        let (old, new) = (a.value(row, column), b.value(row, column));
+        if old != new {
+            same = false;
+            println!(
+                "{:02}:{:02} {} -> {}: {:.2} -> {:.2}",
+                row,
+                column,
+                name(&board.sources, column),
+                name(&board.destinations, row),
+                old,
+                new
+            );
+        }
+    }
+    for lock in &a.locked {
+        if !b.locked.contains(lock) {
+            same = false;
+            println!("unlocked {}", describe_lock(lock));
+        }
+    }
+    for lock in &b.locked {
+        if !a.locked.contains(lock) {
+            same = false;
+            println!("locked {}", describe_lock(lock));
+        }
+    }
+    Ok(same)
+}
+
+fn write_sheet(path: &Path, output: Option<&Path>, options: &Options) -> io::Result<bool> {
+    let mut patch = Patch::load(path)?;
+    if patch.name.is_empty() {
+        patch.name = path.file_stem().map_or(String::new(), |stem| stem.to_string_lossy().into());
+    }
+    let output = output.map_or_else(|| path.with_extension("svg"), Path::to_path_buf);
+    fs::write(&output, sheet::svg(&options.load_board()?, &patch))?;
+    println!("wrote {}", output.display());
+    Ok(true)
+}
+
+fn describe_lock(lock: &Lock) -> String {
+    match lock {
+        Lock::Cell { row, column } => format!("cell {:02}:{:02}", row, column),
+        Lock::Row(row) => format!("row {:02}", row),
+        Lock::Column(column) => format!("column {:02}", column),
+    }
+}
+
+fn validate(path: &Path) -> io::Result<bool> {
+    let board = Board::load(path)?;
+    let (errors, warnings) = board.check();
+
+    for warning in &warnings {
+        println!("warning: {}", warning);
+    }
+    for error in &errors {
+        println!("error: {}", error);
+    }
+    println!(
+        "{}: {} sources, {} destinations, {} errors",
+        board.name,
+        board.sources.len(),
+        board.destinations.len(),
+        errors.len()
+    );
+    Ok(errors.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        std::iter::once("fluff").chain(line.split_whitespace()).map(String::from).collect()
+    }
+
+    #[test]
+    fn parses_commands_and_options() {
+        let (command, options) =
+            parse(&args("send preset.json --port /dev/ttyACM0 -b 9600")).unwrap();
+        assert_eq!(command, Command::Send(PathBuf::from("preset.json")));
+        assert_eq!(options.port.as_deref(), Some("/dev/ttyACM0"));
+        assert_eq!(options.baudrate, 9600);
+
+        let (command, options) = parse(&args("headless --usb-id 16c0:0483")).unwrap();
+        assert_eq!(command, Command::Headless);
+        assert_eq!(options.usb_id, Some((0x16c0, 0x0483)));
+
+        assert_eq!(parse(&args("")).unwrap().0, Command::Run);
+        assert_eq!(parse(&args("--help")).unwrap().0, Command::Help);
+    }
+
+    #[test]
+    fn parses_the_legacy_port_form() {
+        let (command, options) = parse(&args("/dev/ttyUSB0 9600")).unwrap();
+        assert_eq!(command, Command::Run);
+        assert_eq!(options.port.as_deref(), Some("/dev/ttyUSB0"));
+        assert_eq!(options.baudrate, 9600);
+
+        let (_, options) = parse(&args("run COM3")).unwrap();
+        assert_eq!(options.port.as_deref(), Some("COM3"));
+    }
+
+    #[test]
+    fn rejects_unknown_arguments() {
+        assert!(parse(&args("-x")).is_err());
+        assert!(parse(&args("--verbose")).is_err());
+        assert!(parse(&args("sned preset.json")).is_err());
+        assert!(parse(&args("send")).is_err());
+        assert!(parse(&args("--usb-id 16c0")).is_err());
+        assert!(parse(&args("/dev/ttyACM0 fast")).is_err());
+    }
+
+    #[test]
+    fn load_board_rejects_an_empty_board() {
+        let path = std::env::temp_dir().join("fluff-empty-board.json");
+        fs::write(&path, r#"{ "name": "empty", "sources": [], "destinations": [] }"#).unwrap();
+        let options = Options { board: Some(path.clone()), ..Options::default() };
+        let error = options.load_board().unwrap_err();
+        fs::remove_file(&path).ok();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,344 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use nannou::prelude::*;
+use nannou_egui::egui;
+
+use crate::patch::{self, Patch};
+use crate::serial_handler::SerialHandler;
+use crate::shader_ui::{FluffUi, MAX_ZOOM};
+use crate::sheet;
+
+const BAUDRATES: [u32; 7] = [9600, 19200, 38400, 57600, 115200, 230400, 460800];
+// space between the panel and a preset thumbnail
+const THUMBNAIL_MARGIN: f32 = 8.0;
+
+// the egui side panel for the connection, presets and display settings
+pub struct ControlPanel {
+    pub open:    bool,
+    ports:       Vec<String>,
+    port_name:   String,
+    baudrate:    u32,
+    preset_dir:  PathBuf,
+    presets:     Vec<String>,
+    preset_name: String,
+    notes:       String,
+    // edited in the panel, so the folder can be changed while running
+    screenshots: String,
+    // loaded when hovered, again when the file changed since, as saving one takes a few frames
+    thumbnails:  HashMap<String, (SystemTime, wgpu::Texture)>,
+    // the hovered preset and where its name is in the panel
+    preview:     Option<(String, egui::Rect)>,
+    width:       f32,
+    connections: Sort,
+}
+
+// the order of the connection list, grouped by destination either way
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Sort {
+    Board,
+    Name,
+    Value,
+}
+
+impl ControlPanel {
+    pub fn new(port: &SerialHandler, preset_dir: &Path, screenshot_dir: &Path) -> Self {
+        ControlPanel {
+            open:        true,
+            ports:       SerialHandler::available_ports(),
+            port_name:   port.port_name().to_owned(),
+            baudrate:    port.baudrate(),
+            preset_dir:  preset_dir.to_owned(),
+            presets:     patch::preset_names(preset_dir),
+            preset_name: String::new(),
+            notes:       String::new(),
+            screenshots: screenshot_dir.display().to_string(),
+            thumbnails:  HashMap::new(),
+            preview:     None,
+            width:       0.0,
+            connections: Sort::Board,
+        }
+    }
+
+    pub fn screenshot_dir(&self) -> &Path { Path::new(&self.screenshots) }
+
+    pub fn set_ports(&mut self, ports: Vec<String>, selected: &str) {
+        self.ports = ports;
+        self.port_name = selected.to_owned();
+    }
+
+    // returns the width taken by the panel, zero when it is closed
+    pub fn show(
+        &mut self,
+        ctx: &egui::CtxRef,
+        app: &App,
+        matrix: &mut FluffUi,
+        port: &mut SerialHandler,
+    ) -> f32 {
+        // set again while a preset name is hovered
+        self.preview = None;
+        if !self.open {
+            return 0.0;
+        }
+        let panel = egui::SidePanel::left("control_panel").resizable(false).show(ctx, |ui| {
+            ui.collapsing("Connection", |ui| self.connection(ui, matrix, port));
+            ui.collapsing("Presets", |ui| self.presets(ui, app, matrix));
+            ui.collapsing("Connections", |ui| self.connections(ui, matrix));
+            ui.collapsing("Display", |ui| self.display(ui, app, matrix));
+        });
+        self.width = panel.response.rect.width();
+        self.width
+    }
+
+    // the thumbnail of the hovered preset, beside the panel at the height of its name
+    pub fn draw_preview(&self, draw: &Draw, window: Rect) {
+        let (texture, row) = match &self.preview {
+            Some((name, row)) => match self.thumbnails.get(name) {
+                Some((_, texture)) => (texture, row),
+                _ => return,
+            },
+            None => return,
+        };
+        let [width, height] = texture.size();
+        let size = vec2(width as f32, height as f32);
+        // egui measures down from the top left corner
+        let left = window.left() + self.width + THUMBNAIL_MARGIN;
+        let top_left = vec2(left, window.top() - row.top());
+        draw.texture(texture).xy(top_left + size * vec2(0.5, -0.5)).wh(size);
+    }
+
+    fn connection(&mut self, ui: &mut egui::Ui, matrix: &mut FluffUi, port: &mut SerialHandler) {
+        egui::ComboBox::from_label("port").selected_text(self.port_name.clone()).show_ui(ui, |ui| {
+            for name in &self.ports {
+                ui.selectable_value(&mut self.port_name, name.clone(), name.as_str());
+            }
+        });
+        egui::ComboBox::from_label("baud").selected_text(self.baudrate.to_string()).show_ui(
+            ui,
+            |ui| {
+                for baudrate in BAUDRATES {
+                    ui.selectable_value(&mut self.baudrate, baudrate, baudrate.to_string());
+                }
+            },
+        );
+
+        ui.horizontal(|ui| {
+            if ui.button("refresh").clicked() {
+                self.ports = SerialHandler::available_ports();
+            }
+            if port.is_connected() {
+                if ui.button("disconnect").clicked() {
+                    port.disconnect();
+                }
+            } else if ui.button("connect").clicked() {
+                port.set_port(&self.port_name, self.baudrate);
+                match port.connect() {
+                    Ok(_) => matrix.refresh(),
+                    Err(e) => println!("Failed to open port: {}", e),
+                }
+            }
+        });
+
+        if port.is_connected() {
+            let status = format!("connected to {} at {}", port.port_name(), port.baudrate());
+            ui.colored_label(egui::Color32::GREEN, status);
+        } else {
+            let status = port.last_error().unwrap_or("disconnected").to_owned();
+            ui.colored_label(egui::Color32::RED, status);
+        }
+    }
+
+    fn presets(&mut self, ui: &mut egui::Ui, app: &App, matrix: &mut FluffUi) {
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.preset_name);
+            if ui.button("save").clicked() && !self.preset_name.is_empty() {
+                matrix.set_patch_info(&self.preset_name, &self.notes);
+                let path = patch::preset_path(&self.preset_dir, &self.preset_name);
+                let saved = fs::create_dir_all(&self.preset_dir)
+                    .and_then(|_| matrix.patch().save(&path));
+                match saved {
+                    Ok(_) => {
+                        let thumbnail = patch::thumbnail_path(&self.preset_dir, &self.preset_name);
+                        matrix.save_thumbnail(app, thumbnail);
+                        self.thumbnails.remove(&self.preset_name);
+                    }
+                    Err(e) => println!("Failed to save preset: {}", e),
+                }
+                self.presets = patch::preset_names(&self.preset_dir);
+            }
+        });
+        ui.label("notes");
+        ui.text_edit_multiline(&mut self.notes);
+        if ui.button("export sheet").clicked() {
+            self.export_sheet(matrix);
+        }
+
+        let mut changed = false;
+        let mut preview = None;
+        for name in &self.presets {
+            let path = patch::preset_path(&self.preset_dir, name);
+            ui.horizontal(|ui| {
+                if ui.button("load").clicked() {
+                    match Patch::load(&path) {
+                        Ok(patch) => {
+                            self.preset_name = name.clone();
+                            self.notes = patch.notes.clone();
+                            matrix.load_patch(&patch);
+                        }
+                        Err(e) => println!("Failed to load preset: {}", e),
+                    }
+                }
+                if ui.button("delete").clicked() {
+                    if let Err(e) = fs::remove_file(&path) {
+                        println!("Failed to delete preset: {}", e);
+                    }
+                    // older presets have no thumbnail
+                    fs::remove_file(patch::thumbnail_path(&self.preset_dir, name)).ok();
+                    changed = true;
+                }
+                let label = ui.label(name.as_str());
+                if label.hovered() {
+                    preview = Some((name.clone(), label.rect));
+                }
+            });
+        }
+        if changed {
+            self.presets = patch::preset_names(&self.preset_dir);
+            self.thumbnails.clear();
+        }
+
+        if let Some((name, _)) = &preview {
+            self.load_thumbnail(app, name);
+        }
+        self.preview = preview;
+    }
+
+    // misses aren't kept, the thumbnail of a preset that was just saved may not be written yet
+    fn load_thumbnail(&mut self, app: &App, name: &str) {
+        let path = patch::thumbnail_path(&self.preset_dir, name);
+        let modified = match fs::metadata(&path).and_then(|metadata| metadata.modified()) {
+            Ok(modified) => modified,
+            Err(_) => return,
+        };
+        if self.thumbnails.get(name).is_some_and(|(loaded, _)| *loaded == modified) {
+            return;
+        }
+        if let Ok(texture) = wgpu::Texture::from_path(app, path) {
+            self.thumbnails.insert(name.to_owned(), (modified, texture));
+        }
+    }
+
+    // a printable sheet of what is on screen, next to the presets
+    fn export_sheet(&self, matrix: &mut FluffUi) {
+        let name = if self.preset_name.is_empty() { "patch" } else { &self.preset_name };
+        matrix.set_patch_info(name, &self.notes);
+        let path = patch::sheet_path(&self.preset_dir, name);
+        let svg = sheet::svg(matrix.matrix().board(), &matrix.patch());
+        match fs::create_dir_all(&self.preset_dir).and_then(|_| fs::write(&path, svg)) {
+            Ok(_) => println!("wrote {}", path.display()),
+            Err(e) => println!("Failed to export sheet: {}", e),
+        }
+    }
+
+    // every pin that is set, clicking one jumps to its cell and x clears it
+    fn connections(&mut self, ui: &mut egui::Ui, matrix: &mut FluffUi) {
+        let sort = format!("{:?}", self.connections);
+        egui::ComboBox::from_label("sort").selected_text(sort).show_ui(ui, |ui| {
+            for sort in [Sort::Board, Sort::Name, Sort::Value] {
+                ui.selectable_value(&mut self.connections, sort, format!("{:?}", sort));
+            }
+        });
+
+        let patch = matrix.matrix();
+        let board = patch.board();
+        // (destination, sources) in serial row and column indices
+        let mut destinations: Vec<(usize, Vec<usize>)> = (0..patch.rows())
+            .map(|row| {
+                (row, (0..patch.columns()).filter(|i| patch.value(row, *i) != 0.0).collect())
+            })
+            .filter(|(_, sources)| !sources.is_empty())
+            .collect();
+        match self.connections {
+            Sort::Board => (),
+            Sort::Name => {
+                let (destination, source) =
+                    (|i: usize| &board.destinations[i].name, |i: usize| &board.sources[i].name);
+                destinations.sort_by(|a, b| destination(a.0).cmp(destination(b.0)));
+                for (_, sources) in &mut destinations {
+                    sources.sort_by(|a, b| source(*a).cmp(source(*b)));
+                }
+            }
+            Sort::Value =>
+                for (row, sources) in &mut destinations {
+                    let value = |column: &usize| patch.value(*row, *column);
+                    sources.sort_by(|a, b| value(b).total_cmp(&value(a)));
+                },
+        }
+
+        if destinations.is_empty() {
+            ui.label("nothing is patched");
+        }
+        let mut jump = None;
+        let mut clear = None;
+        for (row, sources) in &destinations {
+            ui.label(board.destinations[*row].name.as_str());
+            for column in sources {
+                let value = patch.value(*row, *column);
+                let level = if value >= 1.0 { "on".to_string() } else { format!("{:.2}", value) };
+                let (source, destination) = (&board.sources[*column], &board.destinations[*row]);
+                let text = format!("{} → {} [{}]", source.name, destination.name, level);
+                ui.horizontal(|ui| {
+                    if patch.is_locked(*row, *column) {
+                        ui.label("locked");
+                    } else if ui.small_button("x").clicked() {
+                        clear = Some((*row, *column));
+                    }
+                    if ui.selectable_label(false, text).clicked() {
+                        jump = Some((*row, *column));
+                    }
+                });
+            }
+        }
+
+        if let Some((row, column)) = clear {
+            matrix.set_cell(row, column, 0.0);
+        }
+        if let Some((row, column)) = jump {
+            matrix.jump_to(row, column);
+        }
+    }
+
+    fn display(&mut self, ui: &mut egui::Ui, app: &App, matrix: &mut FluffUi) {
+        let mut zoom = matrix.zoom();
+        if ui.add(egui::Slider::new(&mut zoom, 1.0..=MAX_ZOOM).text("zoom")).changed() {
+            matrix.set_zoom(zoom);
+        }
+        if ui.button("reset view").clicked() {
+            matrix.reset_view();
+        }
+
+        let mut search = matrix.search().to_owned();
+        let mut hide_unmatched = matrix.hide_unmatched();
+        ui.horizontal(|ui| {
+            ui.label("search");
+            ui.text_edit_singleline(&mut search);
+        });
+        ui.checkbox(&mut hide_unmatched, "hide what doesn't match");
+        matrix.set_search(&search, hide_unmatched);
+
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.screenshots);
+            if ui.button("screenshot").clicked() {
+                matrix.save_screenshot(app, self.screenshot_dir());
+            }
+        });
+
+        let window = app.main_window();
+        let mut fullscreen = window.is_fullscreen();
+        if ui.checkbox(&mut fullscreen, "fullscreen").changed() {
+            window.set_fullscreen(fullscreen);
+        }
+    }
+}
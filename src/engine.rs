@@ -0,0 +1,209 @@
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::board::{Board, Label};
+use crate::matrix::PatchMatrix;
+use crate::patch::Patch;
+use crate::protocol;
+
+// the patch matrix and serial output without any window, driven by text commands
+pub struct Engine {
+    matrix: PatchMatrix,
+    output: Box<dyn Write + Send>,
+    // the last write failed, so the board missed some of the changes
+    failed: bool,
+}
+
+pub const COMMANDS: &str = "commands:
+    set <source> <destination> [value]  connect, value 1 by default
+    clear <source> <destination>        disconnect
+    toggle <source> <destination>       connect or disconnect
+    reset                               disconnect everything that isn't locked
+    load <patch>                        recall a patch file
+    save <patch>                        save the current patch
+    send                                send the whole matrix again
+    list                                print the connections
+    help                                show this message
+    quit                                stop
+sources and destinations are label names or indices";
+
+pub enum Reply {
+    Ok(String),
+    Quit,
+}
+
+impl Engine {
+    // starts out with the default locks, like the window does
+    pub fn new(board: Board, output: Box<dyn Write + Send>) -> Self {
+        let mut matrix = PatchMatrix::new(board);
+        matrix.lock_defaults();
+        Engine { matrix, output, failed: false }
+    }
+
+    pub fn matrix(&self) -> &PatchMatrix { &self.matrix }
+    pub fn output_failed(&self) -> bool { self.failed }
+
+    pub fn set_output(&mut self, output: Box<dyn Write + Send>) { self.output = output; }
+
+    // sends the whole matrix, so the board matches the engine
+    pub fn send_all(&mut self) -> io::Result<()> {
+        self.matrix.refresh();
+        self.send()
+    }
+
+    // row and column follow the serial addressing, locked cells are left alone
+    pub fn set(&mut self, row: usize, column: usize, value: f32) -> io::Result<bool> {
+        let changed = self.matrix.set(row, column, value);
+        self.send()?;
+        Ok(changed)
+    }
+
+    // recalls the values of a patch, keeping locked cells and adding the patch locks
+    pub fn load(&mut self, patch: &Patch) -> io::Result<()> {
+        self.matrix.apply(patch);
+        self.send()
+    }
+
+    // writes out whatever changed in the matrix, after a failed write the whole matrix goes
+    // out again as there is no telling how much of it the board got
+    fn send(&mut self) -> io::Result<()> {
+        if self.failed {
+            self.matrix.refresh();
+        }
+        let lines = protocol::change_lines(&self.matrix.take_changes());
+        if lines.is_empty() {
+            return Ok(());
+        }
+        let result = self.output.write_all(lines.as_bytes()).and_then(|_| self.output.flush());
+        self.failed = result.is_err();
+        result
+    }
+
+    pub fn execute(&mut self, line: &str) -> Result<Reply, String> {
+        let words: Vec<_> = line.split_whitespace().collect();
+        let reply = match words.as_slice() {
+            [] => String::new(),
+            ["set", source, destination] => self.connect(source, destination, "1")?,
+            ["set", source, destination, value] => self.connect(source, destination, value)?,
+            ["clear", source, destination] => self.connect(source, destination, "0")?,
+            ["toggle", source, destination] => {
+                let (row, column) = self.address(source, destination)?;
+                let value = if self.matrix.value(row, column) != 0.0 { "0" } else { "1" };
+                self.connect(source, destination, value)?
+            }
+            ["reset"] => {
+                self.load(&Patch::default()).map_err(|e| e.to_string())?;
+                "ok".to_string()
+            }
+            ["load", path] => {
+                let patch = Patch::load(Path::new(path)).map_err(|e| e.to_string())?;
+                self.load(&patch).map_err(|e| e.to_string())?;
+                "ok".to_string()
+            }
+            ["save", path] => {
+                self.matrix.patch().save(Path::new(path)).map_err(|e| e.to_string())?;
+                "ok".to_string()
+            }
+            ["send"] => {
+                self.send_all().map_err(|e| e.to_string())?;
+                "ok".to_string()
+            }
+            ["list"] => self.list(),
+            ["help"] => COMMANDS.to_string(),
+            ["quit"] | ["exit"] => return Ok(Reply::Quit),
+            _ => return Err(format!("unknown command {}, try help", line.trim())),
+        };
+        Ok(Reply::Ok(reply))
+    }
+
+    fn connect(&mut self, source: &str, destination: &str, value: &str) -> Result<String, String> {
+        let (row, column) = self.address(source, destination)?;
+        let value: f32 = value.parse().map_err(|_| format!("{} isn't a number", value))?;
+        if self.matrix.is_locked(row, column) {
+            return Err(format!("{} -> {} is locked", source, destination));
+        }
+        self.set(row, column, value).map_err(|e| e.to_string())?;
+        Ok("ok".to_string())
+    }
+
+    fn address(&self, source: &str, destination: &str) -> Result<(usize, usize), String> {
+        let board = self.matrix.board();
+        let column = find_label(&board.sources, source)
+            .ok_or(format!("no source called {}", source))?;
+        let row = find_label(&board.destinations, destination)
+            .ok_or(format!("no destination called {}", destination))?;
+        Ok((row, column))
+    }
+
+    fn list(&self) -> String {
+        let board = self.matrix.board();
+        let name = |labels: &[Label], i: usize| {
+            labels.get(i).map_or(format!("{:02}", i), |label| label.name.clone())
+        };
+        self.matrix
+            .patch()
+            .cells
+            .iter()
+            .map(|cell| {
+                format!(
+                    "{} -> {} {:.2}",
+                    name(&board.sources, cell.column),
+                    name(&board.destinations, cell.row),
+                    cell.value
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+// by index or by the first label with the name
+fn find_label(labels: &[Label], name: &str) -> Option<usize> {
+    match name.parse::<usize>() {
+        Ok(i) if i < labels.len() => Some(i),
+        _ => labels.iter().position(|label| label.name == name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    // keeps what was written, fails while `broken` is set
+    #[derive(Clone, Default)]
+    struct Output {
+        written: Arc<Mutex<String>>,
+        broken:  Arc<Mutex<bool>>,
+    }
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if *self.broken.lock().unwrap() {
+                return Err(io::Error::new(io::ErrorKind::BrokenPipe, "unplugged"));
+            }
+            self.written.lock().unwrap().push_str(&String::from_utf8_lossy(buf));
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    }
+
+    #[test]
+    fn failed_writes_are_sent_again() {
+        let output = Output::default();
+        let mut engine = Engine::new(Board::spectre(), Box::new(output.clone()));
+
+        *output.broken.lock().unwrap() = true;
+        assert!(engine.set(1, 2, 1.0).is_err());
+        assert!(engine.output_failed());
+
+        *output.broken.lock().unwrap() = false;
+        engine.set(3, 4, 1.0).unwrap();
+        assert!(!engine.output_failed());
+        let written = output.written.lock().unwrap();
+        assert!(written.contains("01:02:1\n"));
+        assert!(written.contains("03:04:1\n"));
+    }
+}
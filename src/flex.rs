@@ -0,0 +1,162 @@
+use nannou_core::prelude::*;
+
+use crate::sub_divide::SubdivideExt;
+
+// the flexbox maths, without the entities, so it works without the window
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Orientation {
+    Horizontal,
+    Vertical,
+}
+
+// how much of its group a child takes along the group's direction, children without one
+// take a weight of 1
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Flex {
+    pub size: Size,
+    pub min:  f32,
+    pub max:  f32,
+}
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Size {
+    // a share of what is left once the fixed children have their space
+    Weight(f32),
+    Fixed(f32),
+}
+
+impl Flex {
+    pub fn weight(weight: f32) -> Self { Flex { size: Size::Weight(weight), ..Flex::default() } }
+    pub fn fixed(size: f32) -> Self { Flex { size: Size::Fixed(size), ..Flex::default() } }
+    pub fn clamp(self, min: f32, max: f32) -> Self { Flex { min, max, ..self } }
+    fn limit(&self, size: f32) -> f32 { size.max(self.min).min(self.max) }
+}
+
+impl Default for Flex {
+    fn default() -> Self { Flex { size: Size::Weight(1.0), min: 0.0, max: f32::INFINITY } }
+}
+
+// space inside a group's edges and between its children, groups without it are packed tight
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Spacing {
+    pub padding: f32,
+    pub gap:     f32,
+    // where the children go when they don't fill the group
+    pub align:   Align,
+}
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Align {
+    Start,
+    Center,
+    End,
+}
+
+impl Default for Spacing {
+    fn default() -> Self { Spacing { padding: 0.0, gap: 0.0, align: Align::Start } }
+}
+
+// the bounds of each child, the gaps and any space the children leave go in as weights of their
+// own so the divide helpers place everything in one go
+pub fn layout(
+    shape: Rect,
+    orientation: Orientation,
+    spacing: Spacing,
+    flexes: &[Flex],
+) -> Vec<Rect> {
+    let inner = shape.pad(spacing.padding);
+    let length = match orientation {
+        Orientation::Horizontal => inner.w(),
+        Orientation::Vertical => inner.h(),
+    };
+    let gaps = spacing.gap * flexes.len().saturating_sub(1) as f32;
+    let sizes = flex_sizes(flexes, (length - gaps).max(0.0));
+    let free = (length - gaps - sizes.iter().sum::<f32>()).max(0.0);
+    let lead = match spacing.align {
+        Align::Start => 0.0,
+        Align::Center => free / 2.0,
+        Align::End => free,
+    };
+
+    let mut weights = vec![lead];
+    for (i, size) in sizes.iter().enumerate() {
+        if i > 0 {
+            weights.push(spacing.gap);
+        }
+        weights.push(*size);
+    }
+    weights.push(free - lead);
+
+    let slots = match orientation {
+        Orientation::Horizontal => inner.divide_columns_weighted(&weights),
+        Orientation::Vertical => inner.divide_rows_weighted(&weights),
+    };
+    // the children are every other slot, after the leading space
+    slots.into_iter().skip(1).step_by(2).take(flexes.len()).collect()
+}
+
+// fixed sizes first, then the weights share out the rest, a child held by its min or max keeps
+// that size and the others share again without it
+fn flex_sizes(flexes: &[Flex], length: f32) -> Vec<f32> {
+    let mut sizes = vec![0.0; flexes.len()];
+    let mut settled = vec![false; flexes.len()];
+    for (i, flex) in flexes.iter().enumerate() {
+        if let Size::Fixed(size) = flex.size {
+            sizes[i] = flex.limit(size);
+            settled[i] = true;
+        }
+    }
+    loop {
+        let used: f32 = (0..flexes.len()).filter(|i| settled[*i]).map(|i| sizes[i]).sum();
+        let weight = |i: usize| match flexes[i].size {
+            Size::Weight(weight) => weight,
+            Size::Fixed(_) => 0.0,
+        };
+        let open: Vec<_> = (0..flexes.len()).filter(|i| !settled[*i]).collect();
+        let total: f32 = open.iter().map(|i| weight(*i)).sum();
+        let share = if total > 0.0 { (length - used).max(0.0) / total } else { 0.0 };
+
+        let mut held = false;
+        for i in open {
+            let size = weight(i) * share;
+            sizes[i] = flexes[i].limit(size);
+            if sizes[i] != size {
+                settled[i] = true;
+                held = true;
+            }
+        }
+        if !held {
+            return sizes;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weights_share_the_length() {
+        let shape = Rect::from_w_h(100.0, 10.0);
+        let flexes = [Flex::weight(1.0), Flex::weight(3.0)];
+        let placed = layout(shape, Orientation::Horizontal, Spacing::default(), &flexes);
+        assert_eq!(placed.iter().map(|rect| rect.w()).collect::<Vec<_>>(), vec![25.0, 75.0]);
+        assert_eq!(placed[0].x(), -37.5);
+        assert_eq!(placed[1].x(), 12.5);
+    }
+
+    #[test]
+    fn fixed_and_clamped_sizes_come_first() {
+        let flexes = [Flex::fixed(20.0), Flex::weight(1.0).clamp(0.0, 30.0), Flex::weight(1.0)];
+        assert_eq!(flex_sizes(&flexes, 100.0), vec![20.0, 30.0, 50.0]);
+    }
+
+    #[test]
+    fn spacing_pads_gaps_and_aligns() {
+        let shape = Rect::from_w_h(20.0, 100.0);
+        let spacing = Spacing { padding: 5.0, gap: 10.0, align: Align::Center };
+        let flexes = [Flex::fixed(20.0), Flex::fixed(20.0)];
+        let placed = layout(shape, Orientation::Vertical, spacing, &flexes);
+        assert_eq!(placed.iter().map(|rect| rect.y()).collect::<Vec<_>>(), vec![15.0, -15.0]);
+        assert!(placed.iter().all(|rect| rect.w() == 10.0 && rect.h() == 20.0));
+    }
+}
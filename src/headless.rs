@@ -0,0 +1,168 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
+
+use crate::cli::Options;
+use crate::engine::{Engine, Reply};
+use crate::patch::Patch;
+use crate::port_scanner::PortScanner;
+use crate::transport::Transport;
+
+const PORT_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+// a command line and where its reply goes
+type Request = (String, Sender<String>);
+
+// runs the engine without a window, commands come from stdin and the `--listen` address
+pub fn run(options: &Options) -> io::Result<bool> {
+    let board = options.load_board()?;
+    let output = open_output(options);
+    let mut engine = Engine::new(board, output);
+
+    if let Some(path) = &options.patch {
+        engine.load(&Patch::load(path)?)?;
+    }
+    engine.send_all()?;
+
+    let (sender, receiver) = mpsc::channel();
+    if let Some(address) = &options.listen {
+        let listener = TcpListener::bind(address)?;
+        eprintln!("listening on {}", address);
+        let sender = sender.clone();
+        thread::spawn(move || accept(listener, sender));
+    }
+    // without a listener closing stdin stops the engine
+    let quit_on_eof = options.listen.is_none();
+    thread::spawn(move || read_stdin(sender, quit_on_eof));
+
+    serve(&mut engine, receiver, options);
+    Ok(true)
+}
+
+// installations boot before the board is plugged in, so wait for it
+fn open_output(options: &Options) -> Box<dyn Write + Send> {
+    let matcher = options.port_matcher();
+    let mut waiting = false;
+    loop {
+        match options.transport.open(options.port.as_deref(), &matcher, options.baudrate) {
+            Ok(output) => return output,
+            Err(e) => {
+                if !waiting {
+                    eprintln!("Failed to open port: {}, waiting for it", e);
+                    waiting = true;
+                }
+                thread::sleep(PORT_RETRY_INTERVAL);
+            }
+        }
+    }
+}
+
+fn serve(engine: &mut Engine, receiver: Receiver<Request>, options: &Options) {
+    let mut scanner = PortScanner::new(options.port_matcher(), PORT_RETRY_INTERVAL);
+    let mut connected = true;
+    loop {
+        match receiver.recv_timeout(PORT_RETRY_INTERVAL) {
+            Ok((line, reply)) => {
+                let (text, quit) = match engine.execute(&line) {
+                    Ok(Reply::Ok(text)) => (text, false),
+                    Ok(Reply::Quit) => ("bye".to_string(), true),
+                    Err(e) => (format!("error: {}", e), false),
+                };
+                reply.send(text).ok();
+                if quit {
+                    return;
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => (),
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+
+        // a failed write means the board is gone, release the port until it shows up again
+        if connected && engine.output_failed() {
+            eprintln!("Lost the port, waiting for it");
+            engine.set_output(Box::new(io::sink()));
+            connected = false;
+        }
+        if !connected && reconnect(engine, options, &mut scanner) {
+            eprintln!("port is back");
+            connected = true;
+        }
+    }
+}
+
+// reopens the port once the scanner sees it again and sends it the whole matrix
+fn reconnect(engine: &mut Engine, options: &Options, scanner: &mut PortScanner) -> bool {
+    scanner.poll();
+    let port = match (options.transport, &options.port) {
+        (Transport::Serial, None) => match scanner.find_match(&scanner.port_names()) {
+            Some(port) => Some(port),
+            None => return false,
+        },
+        (_, port) => port.clone(),
+    };
+    match options.transport.open(port.as_deref(), &scanner.matcher, options.baudrate) {
+        Ok(output) => {
+            engine.set_output(output);
+            if engine.send_all().is_ok() {
+                return true;
+            }
+            engine.set_output(Box::new(io::sink()));
+            false
+        }
+        Err(_) => false,
+    }
+}
+
+fn read_stdin(sender: Sender<Request>, quit_on_eof: bool) {
+    let stdin = io::stdin();
+    for line in stdin.lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        match request(&sender, line) {
+            Some(reply) if !reply.is_empty() => println!("{}", reply),
+            Some(_) => (),
+            None => return,
+        }
+    }
+    if quit_on_eof {
+        request(&sender, "quit".to_string());
+    }
+}
+
+fn accept(listener: TcpListener, sender: Sender<Request>) {
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let sender = sender.clone();
+                thread::spawn(move || {
+                    if let Err(e) = handle_client(stream, sender) {
+                        eprintln!("Failed to talk to client: {}", e);
+                    }
+                });
+            }
+            Err(e) => eprintln!("Failed to accept connection: {}", e),
+        }
+    }
+}
+
+fn handle_client(stream: TcpStream, sender: Sender<Request>) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        match request(&sender, line?) {
+            Some(reply) => writeln!(writer, "{}", reply)?,
+            None => break,
+        }
+    }
+    Ok(())
+}
+
+// none once the engine has stopped
+fn request(sender: &Sender<Request>, line: String) -> Option<String> {
+    let (reply, receiver) = mpsc::channel();
+    sender.send((line, reply)).ok()?;
+    receiver.recv().ok()
+}
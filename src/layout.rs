@@ -0,0 +1,223 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use hecs::*;
+use nannou::prelude::*;
+
+use crate::flex::layout;
+pub use crate::flex::{Align, Flex, Orientation, Size, Spacing};
+
+// a flexbox made of hecs entities, groups share their bounds out between their children, the
+// maths is in `flex`
+
+#[derive(Debug, Clone, Copy)]
+pub struct Bounds {
+    pub shape:  Rect,
+    pub update: bool,
+}
+pub struct NeedsRefresh;
+
+pub struct Group {
+    pub children:   Vec<Entity>,
+    pub orentation: Orientation,
+}
+
+impl Group {
+    pub fn AddChild(&mut self, entity: Entity) { self.children.push(entity); }
+    pub fn AddChildren(&mut self, entity: &Vec<Entity>) { self.children.extend(entity); }
+}
+
+// the root of the layout, resized with the window
+pub struct WindowRect;
+// collapsed by its group, filtered out by the label search for example
+pub struct Hidden;
+
+pub fn system_resize_window(world: &mut World, rect: Rect) {
+    let mut id_group = vec![];
+    for (id, (window, mut bounds)) in &mut world.query::<(&WindowRect, &mut Bounds)>() {
+        bounds.shape = rect;
+        bounds.update = true;
+        id_group.push(id);
+    }
+    for id in id_group {
+        world.insert_one(id, NeedsRefresh).unwrap();
+    }
+}
+pub fn system_vertical_group(world: &mut World, entitys: Vec<Entity>, rect: &Rect) -> Entity {
+    world.spawn((
+        Group { children: entitys, orentation: Orientation::Vertical },
+        Bounds { shape: rect.clone(), update: true },
+        // NeedsRefresh,
+    ))
+}
+pub fn system_horizontal_group(world: &mut World, entitys: Vec<Entity>, rect: &Rect) -> Entity {
+    world.spawn((
+        Group { children: entitys, orentation: Orientation::Horizontal },
+        Bounds { shape: rect.clone(), update: true },
+        NeedsRefresh,
+    ))
+}
+
+// a group tree that can't be laid out, the links at fault are left out and the rest still is
+#[derive(Debug, Clone, PartialEq)]
+pub enum LayoutError {
+    // a child listed by a second group, or twice by the same one, only the group spawned first
+    // places it
+    Shared { child: Entity, first: Entity, second: Entity },
+    // groups that end up inside themselves, none of them are placed
+    Cycle(Vec<Entity>),
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LayoutError::Shared { child, first, second } =>
+                write!(f, "{:?} is a child of both {:?} and {:?}", child, first, second),
+            LayoutError::Cycle(groups) => write!(f, "groups {:?} contain themselves", groups),
+        }
+    }
+}
+
+// one pass from the top groups down, a group is placed again when it needs a refresh or its
+// parent moved it, every group and child is looked at once
+pub fn system_update_flexbox(world: &mut World) -> Vec<LayoutError> {
+    if world.query::<&NeedsRefresh>().iter().next().is_none() {
+        return vec![];
+    }
+    let mut errors = vec![];
+
+    // the group each child belongs to, the one spawned first wins, in id order as hecs goes
+    // through the groups archetype by archetype
+    let mut listed: Vec<_> =
+        world.query::<&Group>().iter().map(|(id, group)| (id, group.children.clone())).collect();
+    listed.sort_by_key(|(id, _)| id.id());
+    let mut parents = HashMap::new();
+    let mut groups = HashMap::new();
+    for (id, listed_children) in listed {
+        let mut children = vec![];
+        for child in &listed_children {
+            match parents.get(child) {
+                Some(first) =>
+                    errors.push(LayoutError::Shared { child: *child, first: *first, second: id }),
+                None => {
+                    parents.insert(*child, id);
+                    children.push(*child);
+                }
+            }
+        }
+        groups.insert(id, children);
+    }
+
+    // groups in a cycle have a parent all the way round, so they are never reached from a top one
+    let mut stack: Vec<_> =
+        groups.keys().filter(|id| !parents.contains_key(*id)).map(|id| (*id, false)).collect();
+    let mut placed = HashSet::new();
+    while let Some((id, moved)) = stack.pop() {
+        placed.insert(id);
+        let refresh = moved || world.get::<&NeedsRefresh>(id).is_ok();
+        let children = &groups[&id];
+        if refresh {
+            place_children(world, id, children);
+        }
+        for child in children {
+            if groups.contains_key(child) {
+                stack.push((*child, refresh));
+            }
+        }
+    }
+
+    let mut cycle: Vec<_> = groups.into_keys().filter(|id| !placed.contains(id)).collect();
+    cycle.sort_by_key(|id| id.id());
+    if !cycle.is_empty() {
+        errors.push(LayoutError::Cycle(cycle));
+    }
+
+    let refreshed: Vec<_> = world.query::<&NeedsRefresh>().iter().map(|(id, _)| id).collect();
+    for id in refreshed {
+        world.remove_one::<NeedsRefresh>(id).unwrap();
+    }
+    errors
+}
+
+fn place_children(world: &mut World, id: Entity, children: &[Entity]) {
+    let (shape, orientation) = match world.query_one_mut::<(&Bounds, &Group)>(id) {
+        Ok((bounds, group)) => (bounds.shape, group.orentation),
+        Err(_) => return,
+    };
+    // hidden children collapse and leave their space to the others
+    let children: Vec<_> = children
+        .iter()
+        .filter(|child| !world.entity(**child).is_ok_and(|child| child.has::<Hidden>()))
+        .collect();
+    let flexes: Vec<_> = children
+        .iter()
+        .map(|child| world.get::<&Flex>(**child).map_or(Flex::default(), |flex| *flex))
+        .collect();
+    let spacing = world.get::<&Spacing>(id).map_or(Spacing::default(), |spacing| *spacing);
+
+    let placed = layout(shape, orientation, spacing, &flexes);
+    for (child_id, new_bounds) in children.into_iter().zip(placed) {
+        if let Ok(mut child_bounds) = world.get::<&mut Bounds>(*child_id) {
+            child_bounds.shape = new_bounds;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaf(world: &mut World) -> Entity {
+        world.spawn((Bounds { shape: Rect::from_w_h(0.0, 0.0), update: false },))
+    }
+
+    fn group(world: &mut World, children: Vec<Entity>, shape: Rect) -> Entity {
+        world.spawn((
+            Group { children, orentation: Orientation::Horizontal },
+            Bounds { shape, update: true },
+            NeedsRefresh,
+        ))
+    }
+
+    fn shape(world: &World, id: Entity) -> Rect { world.get::<&Bounds>(id).unwrap().shape }
+
+    fn assert_shape(world: &World, id: Entity, x: f32, w: f32) {
+        let shape = shape(world, id);
+        assert!((shape.x() - x).abs() < 1e-4, "x is {} instead of {}", shape.x(), x);
+        assert!((shape.w() - w).abs() < 1e-4, "w is {} instead of {}", shape.w(), w);
+    }
+
+    #[test]
+    fn shared_child_goes_to_the_first_group() {
+        let mut world = World::new();
+        let (shared, other) = (leaf(&mut world), leaf(&mut world));
+        let first = group(&mut world, vec![shared], Rect::from_w_h(0.0, 0.0));
+        let second = group(&mut world, vec![shared, other], Rect::from_w_h(0.0, 0.0));
+        // another archetype, which hecs may well go through before the first group's
+        world.insert_one(second, Spacing::default()).unwrap();
+        group(&mut world, vec![first, second], Rect::from_w_h(100.0, 10.0));
+
+        let errors = system_update_flexbox(&mut world);
+        assert_eq!(errors, vec![LayoutError::Shared { child: shared, first, second }]);
+        assert_shape(&world, first, -25.0, 50.0);
+        assert_shape(&world, second, 25.0, 50.0);
+        assert_shape(&world, shared, -25.0, 50.0);
+        assert_shape(&world, other, 25.0, 50.0);
+        assert!(world.query::<&NeedsRefresh>().iter().next().is_none());
+    }
+
+    #[test]
+    fn group_inside_itself_is_left_out() {
+        let mut world = World::new();
+        let (placed, stuck) = (leaf(&mut world), leaf(&mut world));
+        let root = group(&mut world, vec![placed], Rect::from_w_h(100.0, 10.0));
+        let cycle = group(&mut world, vec![stuck], Rect::from_w_h(40.0, 10.0));
+        world.get::<&mut Group>(cycle).unwrap().children.push(cycle);
+
+        let errors = system_update_flexbox(&mut world);
+        assert_eq!(errors, vec![LayoutError::Cycle(vec![cycle])]);
+        assert_shape(&world, root, 0.0, 100.0);
+        assert_shape(&world, placed, 0.0, 100.0);
+        assert_shape(&world, stuck, 0.0, 0.0);
+    }
+}
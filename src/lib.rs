@@ -0,0 +1,26 @@
+// the controller logic, usable without the window by the other binaries and team tools
+pub mod board;
+pub mod engine;
+pub mod flex;
+pub mod matrix;
+pub mod patch;
+pub mod port_scanner;
+pub mod protocol;
+pub mod sheet;
+pub mod sub_divide;
+pub mod transport;
+
+pub mod cli;
+pub mod headless;
+pub mod tui;
+
+#[cfg(feature = "gui")]
+pub mod control_panel;
+#[cfg(feature = "gui")]
+pub mod layout;
+#[cfg(feature = "gui")]
+pub mod serial_handler;
+#[cfg(feature = "gui")]
+pub mod shader_ui;
+
+pub const BAUDRATE: u32 = 115200;
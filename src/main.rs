@@ -2,153 +2,84 @@
 #[cfg(feature = "std")]
 //imports ------------------
 use std::path::Path;
-use std::{env, fs, io, usize};
+use std::path::PathBuf;
+use std::time::Duration;
+use std::{env, fs, io, process, thread, usize};
 
 use nannou::image::open;
 use nannou::prelude::*;
 use nannou::wgpu::Texture;
-
-pub mod shader_ui;
-use shader_ui::FluffUi;
-
-pub mod sub_divide;
-use sub_divide::SubdivideExt;
-
-pub mod serial_handler;
-use serial_handler::SerialHandler;
+use nannou_egui::Egui;
+
+use fluff::board::Board;
+use fluff::cli::{self, Command};
+use fluff::control_panel::ControlPanel;
+use fluff::patch::Patch;
+use fluff::port_scanner::PortScanner;
+use fluff::protocol;
+use fluff::serial_handler::SerialHandler;
+use fluff::shader_ui::FluffUi;
+use fluff::sub_divide::SubdivideExt;
+use fluff::transport::Transport;
 
 use ascii::AsAsciiStr;
 
 //Constants -----
-const PORT_DEFAULT: &str = "/dev/tty.usbmodem142903901";
-// const PORT_DEFAULT: &str = "/dev/tty";
+const PORT_SCAN_INTERVAL: Duration = Duration::from_secs(1);
+
+const SERIAL_DEBUG: bool = true;
 
-pub const BAUDRATE: u32 = 115200;
-pub const SERIAL_DEBUG: bool = true;
+const PRESET_DIR: &str = "presets";
+// space between the window edge and the drop target outline
+const DROP_MARGIN: f32 = 20.0;
+// the preview needs the shader preprocessor and uniforms the window doesn't have yet
+const SHADER_UNSUPPORTED: &str = "shader preview not supported";
 
 //code ---------
-fn main() { nannou::app(controller).update(update).run(); }
+fn main() {
+    let (command, options) = parse_args();
+    if command != Command::Run {
+        process::exit(cli::run(&command, &options));
+    }
+    nannou::app(controller).update(update).run();
+}
+
+fn parse_args() -> (Command, cli::Options) {
+    let args: Vec<_> = env::args().collect();
+    match cli::parse(&args) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, cli::USAGE);
+            process::exit(2);
+        }
+    }
+}
 
 struct Model {
     ui:    FluffUi,
     count: i32,
 
-    port: SerialHandler,
+    port:      SerialHandler,
+    scanner:   PortScanner,
+    transport: Transport,
+
+    patch_path: PathBuf,
+
+    egui:  Egui,
+    panel: ControlPanel,
+
+    // what dropping the file held over the window would do, shown until it is dropped
+    drop_caption: Option<String>,
 }
 
-fn controller(app: &App) -> Model {
-    let row_lables: Vec<&str> = vec![
-        "invert_x_0",
-        "invert_x_1",
-        "invert_x_2",
-        "invert_x_3",
-        "invert_x_4",
-        "invert_x_5",
-        "invert_x_6",
-        "invert_x_7",
-        "invert_x_8",
-        "invert_y_0",
-        "invert_y_1",
-        "invert_y_2",
-        "invert_y_3",
-        "invert_y_4",
-        "invert_y_5",
-        "invert_y_6",
-        "invert_y_7",
-        "invert_y_8",
-        "overlay_gate_1_dis",
-        "overlay_gate_1_sig",
-        "overlay_gate_2_dis",
-        "overlay_gate_2_sig",
-        "overlay_gate_3_dis",
-        "overlay_gate_3_sig",
-        "overlay_gate_4_dis",
-        "overlay_gate_4_sig",
-        "invert_a",
-        "invert_b",
-        "invert_c",
-        "invert_d",
-        "edge",
-        "delay",
-        "flip_flop_+",
-        "flip_flop_-",
-        "to_acm_fast",
-        "to_acm_slow",
-        "out_a_luma_0",
-        "out_a_luma_1",
-        "out_a_luma_2",
-        "out_a_luma_3",
-        "out_a_col1_0",
-        "out_a_col1_1",
-        "out_a_col1_2",
-        "out_a_col2_0",
-        "out_a_col2_1",
-        "out_a_col2_2",
-        "out_b_luma_0",
-        "out_b_luma_1",
-        "out_b_luma_2",
-        "out_b_luma_3",
-        "out_b_col1_0",
-        "out_b_col1_1",
-        "out_b_col1_2",
-        "out_b_col2_0",
-        "out_b_col2_1",
-        "out_b_col2_2",
-        "col_swap",
-    ];
-    let col_lables: Vec<&str> = vec![
-        "counter_x_0",
-        "counter_x_1",
-        "counter_x_2",
-        "counter_x_3",
-        "counter_x_4",
-        "counter_x_5",
-        "counter_x_6",
-        "counter_x_7",
-        "counter_x_8",
-        "counter_y_0",
-        "counter_y_1",
-        "counter_y_2",
-        "counter_y_3",
-        "counter_y_4",
-        "counter_y_5",
-        "counter_y_6",
-        "counter_y_7",
-        "counter_y_8",
-        "slow_count_6",
-        "slow_count_3",
-        "slow_count_1.5",
-        "slow_count_.8",
-        "slow_count_.4",
-        "slow_count_.2",
-        "overlay_1",
-        "overlay_2",
-        "overlay_3",
-        "overlay_4",
-        "invert_a",
-        "invert_b",
-        "invert_c",
-        "invert_d",
-        "edge_thin_+",
-        "edge_thin_-",
-        "edge_wide_+",
-        "edge_wide_-",
-        "delay",
-        "flip_flop_+",
-        "flip_flop_-",
-        "n/a",
-        "n/a",
-        "n/a",
-        "n/a",
-        "comp_0",
-        "comp_1",
-        "comp_2",
-        "comp_3",
-        "comp_4",
-        "comp_5",
-        "comp_6",
-    ];
+// what a file dropped on the window holds, boards and patches are both json so they are told
+// apart by what they parse as
+enum Dropped {
+    Board(Board),
+    Patch(Patch),
+}
 
+fn controller(app: &App) -> Model {
     //setup window and device
     let wgpu_limits = wgpu::Limits { max_bind_groups: 8, ..Default::default() };
 
@@ -158,39 +89,48 @@ fn controller(app: &App) -> Model {
         limits:   wgpu_limits,
     };
 
-    app.new_window()
+    let window_id = app
+        .new_window()
         .size(1800, 900)
         .device_descriptor(device)
         // .mouse_wheel(scroll_event)
         .event(event_fn)
+        .raw_event(raw_window_event)
         .msaa_samples(1)
         .view(view)
         .build()
         .unwrap();
 
-    let window = app.main_window();
+    // only update and redraw on input, the port scan is woken up below
+    app.set_loop_mode(LoopMode::Wait);
+    let proxy = app.create_proxy();
+    thread::spawn(move || loop {
+        thread::sleep(PORT_SCAN_INTERVAL);
+        if proxy.wakeup().is_err() {
+            break;
+        }
+    });
+
+    let window = app.window(window_id).unwrap();
+    let egui = Egui::from_window(&window);
     let (x, y) = window.inner_size_pixels();
     let dim = UVec2::new(x, y);
 
     //serial stuff
     SerialHandler::print_avaliable_ports();
-    let args: Vec<_> = env::args().collect();
-
-    let port_name = if args.len() > 1 { &args[1] } else { PORT_DEFAULT };
-
-    let baud_rate: u32 = if args.len() > 2 {
-        match &args[2].parse::<u32>() {
-            Ok(b) => *b,
-            Err(_) => {
-                println!("second argument must be an unsigned integer");
-                BAUDRATE
-            }
+    let (_, options) = parse_args();
+
+    let port_name = options.port.clone().unwrap_or_default();
+    let mut port = SerialHandler::new(&port_name, options.baudrate, SERIAL_DEBUG);
+    // ports that aren't enumerated, like pseudo terminals, still get opened
+    if !port_name.is_empty() && options.transport == Transport::Serial {
+        if let Err(e) = port.connect() {
+            println!("Failed to open port: {}", e);
         }
-    } else {
-        BAUDRATE
-    };
-
-    let mut port = SerialHandler::new(port_name, baud_rate, SERIAL_DEBUG);
+    }
+    let scanner = PortScanner::new(options.port_matcher(), PORT_SCAN_INTERVAL);
+    let panel =
+        ControlPanel::new(&port, std::path::Path::new(PRESET_DIR), &options.screenshot_dir());
 
     //setup shader model
     let path = app.assets_path().unwrap().join("happy-tree.png");
@@ -212,20 +152,100 @@ fn controller(app: &App) -> Model {
         .unwrap();
     // let shader_model = ShaderModel::new(shader_paths, &app, &image_texture);
 
-    let ui = FluffUi::new(app, &col_lables, &row_lables);
+    let board = options.load_board().unwrap_or_else(|e| {
+        println!("Failed to load board: {}", e);
+        Board::spectre()
+    });
+
+    let mut ui = FluffUi::new(app, &board);
+
+    let patch_path = options.patch_path();
+    if options.patch.is_some() {
+        match Patch::load(&patch_path) {
+            Ok(patch) => ui.load_patch(&patch),
+            Err(e) => println!("Failed to load patch: {}", e),
+        }
+    }
+
+    Model {
+        ui,
+        count: 30,
+        port,
+        scanner,
+        transport: options.transport,
+        patch_path,
+        egui,
+        panel,
+        drop_caption: None,
+    }
+}
+
+fn is_shader(path: &std::path::Path) -> bool { path.extension().is_some_and(|ext| ext == "wgsl") }
+
+fn read_dropped(path: &std::path::Path) -> Result<Dropped, String> {
+    if is_shader(path) {
+        return Err(SHADER_UNSUPPORTED.to_string());
+    }
+    if let Ok(board) = Board::load(path) {
+        return Ok(Dropped::Board(board));
+    }
+    Patch::load(path).map(Dropped::Patch).map_err(|e| format!("not a board or a patch, {}", e))
+}
+
+fn drop_caption(path: &std::path::Path) -> String {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    match read_dropped(path) {
+        Ok(Dropped::Board(board)) => format!("drop to switch to the {} board", board.name),
+        Ok(Dropped::Patch(_)) => format!("drop to load {}", name),
+        Err(_) if is_shader(path) => format!("{}: {}", name, SHADER_UNSUPPORTED),
+        Err(_) => format!("{} isn't a board or a patch", name),
+    }
+}
 
-    Model { ui, count: 30, port }
+// patches are loaded like a preset, a board replaces the matrix and starts it empty
+fn file_dropped(app: &App, model: &mut Model, path: &std::path::Path) {
+    match read_dropped(path) {
+        Ok(Dropped::Patch(patch)) => {
+            model.ui.load_patch(&patch);
+            println!("loaded {}", path.display());
+        }
+        Ok(Dropped::Board(board)) => match board.check() {
+            (errors, _) if !errors.is_empty() =>
+                println!("Failed to load board: {}", errors.join(", ")),
+            _ => {
+                // the new ui only sends its own cells, so every pin of the old board goes off first
+                let old = model.ui.matrix().board().clone();
+                send(model, &protocol::patch_lines(&Patch::default(), &old));
+
+                let mut ui = FluffUi::new(app, &board);
+                ui.set_zoom(model.ui.zoom());
+                ui.set_search(model.ui.search(), model.ui.hide_unmatched());
+                model.ui = ui;
+                println!("switched to the {} board", board.name);
+            }
+        },
+        Err(e) => println!("Failed to load {}: {}", path.display(), e),
+    }
 }
 
 fn update(app: &App, model: &mut Model, update: Update) {
-    model.ui.update(app);
+    if model.transport == Transport::Serial {
+        poll_ports(model);
+    }
 
-    let output_string = model.ui.get_serial_output(app);
+    model.egui.set_elapsed_time(update.since_start);
+    let ctx = model.egui.begin_frame();
+    let inset = model.panel.show(&ctx, app, &mut model.ui, &mut model.port);
+    drop(ctx);
+    model.ui.set_inset(inset);
 
-    let ascii = output_string.as_ascii_str().unwrap();
-    // print!("{}", ascii);
+    model.ui.update(app);
 
-    model.port.write(ascii);
+    // changes wait in the matrix while the port is down, a reconnect sends everything anyway
+    if model.transport == Transport::Stdout || model.port.is_connected() {
+        let output_string = model.ui.get_serial_output(app);
+        send(model, &output_string);
+    }
     // if !output_string.is_empty() {
 
     // }
@@ -233,8 +253,98 @@ fn update(app: &App, model: &mut Model, update: Update) {
     // shader stuff
 }
 
+fn send(model: &mut Model, output: &str) {
+    let ascii = output.as_ascii_str().unwrap();
+    match model.transport {
+        Transport::Serial => model.port.write(ascii),
+        Transport::Stdout => print!("{}", ascii),
+    }
+}
+
+// follows ports being plugged in and out, connecting to a matching one when it shows up
+fn poll_ports(model: &mut Model) {
+    if let Some(changes) = model.scanner.poll() {
+        for name in &changes.added {
+            println!("port appeared: {}", name);
+        }
+        for name in &changes.removed {
+            println!("port disappeared: {}", name);
+            if name == model.port.port_name() {
+                model.port.disconnect();
+            }
+        }
+        // only reconnect to ports that just showed up, so a manual disconnect sticks
+        if !model.port.is_connected() {
+            if let Some(name) = model.scanner.find_match(&changes.added) {
+                let baudrate = model.port.baudrate();
+                model.port.set_port(&name, baudrate);
+                match model.port.connect() {
+                    Ok(_) => {
+                        println!("connected to {}", name);
+                        model.ui.refresh();
+                    }
+                    Err(e) => println!("Failed to open port: {}", e),
+                }
+            }
+        }
+        model.panel.set_ports(model.scanner.port_names(), model.port.port_name());
+    }
+}
+
+fn raw_window_event(app: &App, model: &mut Model, event: &nannou::winit::event::WindowEvent) {
+    model.egui.handle_raw_event(event);
+    // nannou's touch event leaves out how hard the screen is pressed
+    if let nannou::winit::event::WindowEvent::Touch(touch) = event {
+        if let Some(force) = touch.force {
+            model.ui.set_touch_force(touch.id, force.normalized() as f32);
+        }
+    }
+}
+
+// true when the event is meant for the control panel rather than the matrix
+fn panel_has_input(model: &Model, event: &WindowEvent) -> bool {
+    let ctx = model.egui.ctx();
+    match event {
+        MousePressed(_) | MouseWheel(..) => ctx.is_pointer_over_area(),
+        KeyPressed(_) | ReceivedCharacter(_) => ctx.wants_keyboard_input(),
+        _ => false,
+    }
+}
+
 fn event_fn(app: &App, model: &mut Model, event: WindowEvent) {
+    if panel_has_input(model, &event) {
+        return;
+    }
+    match &event {
+        KeyPressed(Key::F1) => model.panel.open = !model.panel.open,
+        HoveredFile(path) => model.drop_caption = Some(drop_caption(path)),
+        HoveredFileCancelled => model.drop_caption = None,
+        DroppedFile(path) => {
+            model.drop_caption = None;
+            file_dropped(app, model, path);
+        }
+        _ => (),
+    }
+
     model.ui.event_handler(app, &event);
+
+    if let KeyPressed(key) = event {
+        if app.keys.mods.ctrl() {
+            let path = &model.patch_path;
+            match key {
+                Key::S => match model.ui.patch().save(path) {
+                    Ok(_) => println!("saved patch to {}", path.display()),
+                    Err(e) => println!("Failed to save patch: {}", e),
+                },
+                Key::O => match Patch::load(path) {
+                    Ok(patch) => model.ui.load_patch(&patch),
+                    Err(e) => println!("Failed to load patch: {}", e),
+                },
+                Key::P => model.ui.save_screenshot(app, model.panel.screenshot_dir()),
+                _ => (),
+            }
+        }
+    }
 }
 
 fn view(app: &App, model: &Model, frame: Frame) {
@@ -244,4 +354,25 @@ fn view(app: &App, model: &Model, frame: Frame) {
     draw.to_frame(app, &frame).unwrap();
 
     model.ui.draw(app, &frame);
+
+    let draw = app.draw();
+    model.panel.draw_preview(&draw, app.window_rect());
+    if let Some(caption) = &model.drop_caption {
+        draw_drop_target(&draw, app.window_rect(), caption);
+    }
+    draw.to_frame(app, &frame).unwrap();
+
+    model.egui.draw_to_frame(&frame).unwrap();
+}
+
+// highlights the window while a file is held over it
+fn draw_drop_target(draw: &Draw, window: Rect, caption: &str) {
+    let target = window.pad(DROP_MARGIN);
+    draw.rect()
+        .xy(target.xy())
+        .wh(target.wh())
+        .color(rgba(1.0, 1.0, 0.0, 0.1))
+        .stroke_color(YELLOW)
+        .stroke_weight(4.0);
+    draw.text(caption).xy(target.xy()).wh(target.wh()).font_size(32).color(YELLOW);
 }
@@ -0,0 +1,288 @@
+use crate::board::{Board, Label};
+use crate::patch::{Lock, Patch, PatchCell};
+
+// keep the output stage fixed while patching everything else
+pub const LOCKED_DEFAULT: [&str; 2] = ["out_a_", "out_b_"];
+
+// the patch state of a board, without anything to draw it, indices follow the serial
+// `row:column` addressing with destinations as rows and sources as columns
+pub struct PatchMatrix {
+    board:    Board,
+    name:     String,
+    notes:    String,
+    // one row of source values per destination
+    values:   Vec<f32>,
+    locked:   Vec<Lock>,
+    changes:  Vec<Change>,
+    // counts every change, so readers can tell whether anything happened since they last looked
+    revision: u64,
+}
+
+// what happened to the matrix since the changes were last taken
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Change {
+    Value { row: usize, column: usize, value: f32 },
+    Locked(Lock),
+    Unlocked(Lock),
+}
+
+impl PatchMatrix {
+    pub fn new(board: Board) -> Self {
+        let values = vec![0.0; board.destinations.len() * board.sources.len()];
+        PatchMatrix {
+            board,
+            name: String::new(),
+            notes: String::new(),
+            values,
+            locked: vec![],
+            changes: vec![],
+            revision: 0,
+        }
+    }
+
+    pub fn board(&self) -> &Board { &self.board }
+    pub fn rows(&self) -> usize { self.board.destinations.len() }
+    pub fn columns(&self) -> usize { self.board.sources.len() }
+    pub fn locked(&self) -> &[Lock] { &self.locked }
+    pub fn revision(&self) -> u64 { self.revision }
+    pub fn name(&self) -> &str { &self.name }
+    pub fn notes(&self) -> &str { &self.notes }
+
+    // printed on the patch sheet and saved with the patch
+    pub fn set_info(&mut self, name: &str, notes: &str) {
+        self.name = name.to_owned();
+        self.notes = notes.to_owned();
+    }
+
+    pub fn value(&self, row: usize, column: usize) -> f32 {
+        self.index(row, column).map_or(0.0, |i| self.values[i])
+    }
+
+    pub fn is_locked(&self, row: usize, column: usize) -> bool {
+        self.locked.iter().any(|lock| lock.covers(row, column))
+    }
+
+    // false when the cell is locked, out of range or already at the value
+    pub fn set(&mut self, row: usize, column: usize, value: f32) -> bool {
+        let i = match self.index(row, column) {
+            Some(i) if !self.is_locked(row, column) && self.values[i] != value => i,
+            _ => return false,
+        };
+        self.values[i] = value;
+        self.push(Change::Value { row, column, value });
+        true
+    }
+
+    pub fn lock(&mut self, lock: Lock) {
+        if !self.locked.contains(&lock) {
+            self.locked.push(lock);
+            self.push(Change::Locked(lock));
+        }
+    }
+
+    pub fn unlock(&mut self, lock: Lock) {
+        if self.locked.contains(&lock) {
+            self.locked.retain(|other| *other != lock);
+            self.push(Change::Unlocked(lock));
+        }
+    }
+
+    // locks every source and destination whose name starts with one of `LOCKED_DEFAULT`
+    pub fn lock_defaults(&mut self) {
+        let default =
+            |label: &Label| LOCKED_DEFAULT.iter().any(|prefix| label.name.starts_with(prefix));
+        let mut locks = vec![];
+        for (row, label) in self.board.destinations.iter().enumerate() {
+            if default(label) {
+                locks.push(Lock::Row(row));
+            }
+        }
+        for (column, label) in self.board.sources.iter().enumerate() {
+            if default(label) {
+                locks.push(Lock::Column(column));
+            }
+        }
+        for lock in locks {
+            self.lock(lock);
+        }
+    }
+
+    // replaces the locks, only reporting the ones that changed
+    pub fn set_locked(&mut self, locked: &[Lock]) {
+        for lock in self.locked.clone() {
+            if !locked.contains(&lock) {
+                self.unlock(lock);
+            }
+        }
+        for lock in locked {
+            self.lock(*lock);
+        }
+    }
+
+    pub fn patch(&self) -> Patch {
+        let mut patch = Patch {
+            name:   self.name.clone(),
+            notes:  self.notes.clone(),
+            cells:  vec![],
+            locked: self.locked.clone(),
+        };
+        for row in 0..self.rows() {
+            for column in 0..self.columns() {
+                let value = self.value(row, column);
+                if value != 0.0 {
+                    patch.cells.push(PatchCell { row, column, value });
+                }
+            }
+        }
+        patch
+    }
+
+    // recalls a patch, locked cells keep their value and the patch locks are added
+    pub fn apply(&mut self, patch: &Patch) {
+        self.set_info(&patch.name, &patch.notes);
+        for row in 0..self.rows() {
+            for column in 0..self.columns() {
+                self.set(row, column, patch.value(row, column));
+            }
+        }
+        for lock in &patch.locked {
+            self.lock(*lock);
+        }
+    }
+
+    // reports every cell as changed, so whoever listens gets the whole state again
+    pub fn refresh(&mut self) {
+        for row in 0..self.rows() {
+            for column in 0..self.columns() {
+                let value = self.value(row, column);
+                self.push(Change::Value { row, column, value });
+            }
+        }
+    }
+
+    pub fn take_changes(&mut self) -> Vec<Change> { std::mem::take(&mut self.changes) }
+
+    fn push(&mut self, change: Change) {
+        self.changes.push(change);
+        self.revision += 1;
+    }
+
+    fn index(&self, row: usize, column: usize) -> Option<usize> {
+        if row < self.rows() && column < self.columns() {
+            Some(row * self.columns() + column)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board() -> Board {
+        let labels = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| Label { name: name.to_string(), description: String::new() })
+                .collect()
+        };
+        Board {
+            name:         "test".to_string(),
+            sources:      labels(&["osc", "lfo", "out_a_l"]),
+            destinations: labels(&["vcf", "vca"]),
+        }
+    }
+
+    #[test]
+    fn set_reports_changes() {
+        let mut matrix = PatchMatrix::new(board());
+        assert!(matrix.set(1, 2, 0.5));
+        assert!(!matrix.set(1, 2, 0.5));
+        assert!(!matrix.set(2, 0, 1.0));
+        assert_eq!(matrix.value(1, 2), 0.5);
+        assert_eq!(matrix.take_changes(), vec![Change::Value { row: 1, column: 2, value: 0.5 }]);
+        assert!(matrix.take_changes().is_empty());
+    }
+
+    #[test]
+    fn set_leaves_locked_cells_alone() {
+        let mut matrix = PatchMatrix::new(board());
+        matrix.lock(Lock::Row(0));
+        matrix.lock(Lock::Cell { row: 1, column: 1 });
+        assert!(!matrix.set(0, 2, 1.0));
+        assert!(!matrix.set(1, 1, 1.0));
+        assert!(matrix.set(1, 0, 1.0));
+        assert_eq!(matrix.value(0, 2), 0.0);
+
+        matrix.unlock(Lock::Row(0));
+        assert!(matrix.set(0, 2, 1.0));
+    }
+
+    #[test]
+    fn lock_defaults_locks_the_outputs() {
+        let mut matrix = PatchMatrix::new(board());
+        matrix.lock_defaults();
+        assert_eq!(matrix.locked(), &[Lock::Column(2)]);
+        assert!(!matrix.set(0, 2, 1.0));
+    }
+
+    #[test]
+    fn patch_round_trip() {
+        let mut matrix = PatchMatrix::new(board());
+        matrix.set_info("bass", "slow attack");
+        matrix.set(0, 1, 1.0);
+        matrix.set(1, 2, 0.25);
+        matrix.lock(Lock::Column(0));
+        let patch = matrix.patch();
+
+        let mut other = PatchMatrix::new(board());
+        other.apply(&patch);
+        assert_eq!(other.name(), "bass");
+        assert_eq!(other.notes(), "slow attack");
+        assert_eq!(other.value(0, 1), 1.0);
+        assert_eq!(other.value(1, 2), 0.25);
+        assert_eq!(other.locked(), &[Lock::Column(0)]);
+        assert_eq!(other.patch().cells, patch.cells);
+    }
+
+    #[test]
+    fn apply_keeps_locked_cells() {
+        let mut matrix = PatchMatrix::new(board());
+        matrix.set(0, 0, 1.0);
+        matrix.lock(Lock::Cell { row: 0, column: 0 });
+        matrix.apply(&Patch::default());
+        assert_eq!(matrix.value(0, 0), 1.0);
+    }
+
+    #[test]
+    fn refresh_reports_every_cell() {
+        let mut matrix = PatchMatrix::new(board());
+        matrix.set(1, 1, 1.0);
+        matrix.take_changes();
+        let revision = matrix.revision();
+
+        matrix.refresh();
+        let changes = matrix.take_changes();
+        assert_eq!(changes.len(), 6);
+        assert!(changes.contains(&Change::Value { row: 1, column: 1, value: 1.0 }));
+        assert!(changes.contains(&Change::Value { row: 0, column: 2, value: 0.0 }));
+        assert_eq!(matrix.revision(), revision + 6);
+    }
+
+    #[test]
+    fn take_changes_reports_locks() {
+        let mut matrix = PatchMatrix::new(board());
+        matrix.lock(Lock::Row(1));
+        matrix.lock(Lock::Row(1));
+        matrix.set_locked(&[Lock::Column(0)]);
+        assert_eq!(
+            matrix.take_changes(),
+            vec![
+                Change::Locked(Lock::Row(1)),
+                Change::Unlocked(Lock::Row(1)),
+                Change::Locked(Lock::Column(0)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,87 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+// a saved state of the matrix, indices follow the serial `row:column` addressing
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Patch {
+    #[serde(default)]
+    pub name:   String,
+    #[serde(default)]
+    pub notes:  String,
+    pub cells:  Vec<PatchCell>,
+    #[serde(default)]
+    pub locked: Vec<Lock>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PatchCell {
+    pub row:    usize,
+    pub column: usize,
+    pub value:  f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Lock {
+    Cell { row: usize, column: usize },
+    Row(usize),
+    Column(usize),
+}
+
+impl Patch {
+    pub fn load(path: &Path) -> io::Result<Patch> {
+        let file = fs::read_to_string(path)?;
+        serde_json::from_str(&file).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let file = serde_json::to_string_pretty(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(path, file)
+    }
+
+    pub fn value(&self, row: usize, column: usize) -> f32 {
+        self.cells
+            .iter()
+            .find(|cell| cell.row == row && cell.column == column)
+            .map_or(0.0, |cell| cell.value)
+    }
+
+    pub fn is_locked(&self, row: usize, column: usize) -> bool {
+        self.locked.iter().any(|lock| lock.covers(row, column))
+    }
+}
+
+impl Lock {
+    pub fn covers(&self, row: usize, column: usize) -> bool {
+        match *self {
+            Lock::Cell { row: r, column: c } => r == row && c == column,
+            Lock::Row(r) => r == row,
+            Lock::Column(c) => c == column,
+        }
+    }
+}
+
+// presets are patches saved as `<name>.json` in one folder
+pub fn preset_names(dir: &Path) -> Vec<String> {
+    let mut names: Vec<_> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+                .filter_map(|path| path.file_stem().map(|stem| stem.to_string_lossy().into_owned()))
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+    names
+}
+
+pub fn preset_path(dir: &Path, name: &str) -> PathBuf { dir.join(format!("{}.json", name)) }
+
+pub fn sheet_path(dir: &Path, name: &str) -> PathBuf { dir.join(format!("{}.svg", name)) }
+
+// a picture of the matrix saved next to each preset
+pub fn thumbnail_path(dir: &Path, name: &str) -> PathBuf { dir.join(format!("{}.png", name)) }
@@ -0,0 +1,96 @@
+use std::time::{Duration, Instant};
+
+use serialport::{SerialPortInfo, SerialPortType};
+
+// usb serial names on linux and macos
+pub const PORT_PATTERNS_DEFAULT: [&str; 4] = ["ttyACM", "ttyUSB", "usbmodem", "usbserial"];
+
+// how a port is picked when it shows up, either by usb vid/pid or by part of its name
+#[derive(Debug, Clone, Default)]
+pub struct PortMatcher {
+    pub usb_id:   Option<(u16, u16)>,
+    pub patterns: Vec<String>,
+}
+
+impl PortMatcher {
+    // any usb serial device, or only the given (vid, pid)
+    pub fn usb_serial(usb_id: Option<(u16, u16)>) -> Self {
+        let patterns = PORT_PATTERNS_DEFAULT.iter().map(|pattern| pattern.to_string()).collect();
+        PortMatcher { usb_id, patterns }
+    }
+
+    // a usb id is all that counts when there is one, the name patterns only without it
+    pub fn matches(&self, info: &SerialPortInfo) -> bool {
+        match (self.usb_id, &info.port_type) {
+            (Some((vid, pid)), SerialPortType::UsbPort(usb)) => usb.vid == vid && usb.pid == pid,
+            (Some(_), _) => false,
+            (None, _) =>
+                self.patterns.iter().any(|pattern| info.port_name.contains(pattern.as_str())),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct PortChanges {
+    pub added:   Vec<String>,
+    pub removed: Vec<String>,
+}
+
+// enumerates the serial ports every `interval` and reports devices coming and going
+pub struct PortScanner {
+    pub matcher: PortMatcher,
+    known:       Vec<SerialPortInfo>,
+    interval:    Duration,
+    last_scan:   Option<Instant>,
+}
+
+impl PortScanner {
+    pub fn new(matcher: PortMatcher, interval: Duration) -> Self {
+        PortScanner { matcher, known: vec![], interval, last_scan: None }
+    }
+
+    pub fn port_names(&self) -> Vec<String> {
+        self.known.iter().map(|info| info.port_name.clone()).collect()
+    }
+
+    // none until the interval has passed, the first scan reports every port as added
+    pub fn poll(&mut self) -> Option<PortChanges> {
+        if self.last_scan.is_some_and(|last| last.elapsed() < self.interval) {
+            return None;
+        }
+        self.last_scan = Some(Instant::now());
+
+        let ports = match serialport::available_ports() {
+            Ok(ports) => ports,
+            Err(e) => {
+                println!("Failed to list ports: {}", e);
+                return None;
+            }
+        };
+
+        let names = |ports: &Vec<SerialPortInfo>| -> Vec<String> {
+            ports.iter().map(|info| info.port_name.clone()).collect()
+        };
+        let (old, new) = (names(&self.known), names(&ports));
+        let changes = PortChanges {
+            added:   new.iter().filter(|name| !old.contains(name)).cloned().collect(),
+            removed: old.iter().filter(|name| !new.contains(name)).cloned().collect(),
+        };
+        self.known = ports;
+
+        if changes.added.is_empty() && changes.removed.is_empty() {
+            None
+        } else {
+            Some(changes)
+        }
+    }
+
+    // the first of the given ports that matches
+    pub fn find_match(&self, names: &[String]) -> Option<String> {
+        self.known
+            .iter()
+            .filter(|info| names.contains(&info.port_name))
+            .find(|info| self.matcher.matches(info))
+            .map(|info| info.port_name.clone())
+    }
+}
@@ -0,0 +1,93 @@
+use crate::board::Board;
+use crate::matrix::Change;
+use crate::patch::Patch;
+
+// one `row:column:state` line of the serial protocol
+pub fn line(row: usize, column: usize, state: char) -> String {
+    format!("{:02}:{:02}:{}\n", row, column, state)
+}
+
+// the row, column and state of a line, none if it isn't one
+pub fn parse_line(line: &str) -> Option<(usize, usize, char)> {
+    let mut parts = line.trim().split(':');
+    let row = parts.next()?.parse().ok()?;
+    let column = parts.next()?.parse().ok()?;
+    let state = parts.next()?.chars().next()?;
+    if parts.next().is_some() {
+        return None;
+    }
+    Some((row, column, state))
+}
+
+pub fn state(value: f32) -> char {
+    if value != 0.0 {
+        '1'
+    } else {
+        '0'
+    }
+}
+
+// every cell of the board, so cells missing from the patch get switched off
+pub fn patch_lines(patch: &Patch, board: &Board) -> String {
+    let mut output = String::new();
+    for row in 0..board.destinations.len() {
+        for column in 0..board.sources.len() {
+            output.push_str(&line(row, column, state(patch.value(row, column))));
+        }
+    }
+    output
+}
+
+// the value changes, locks only matter to the controller
+pub fn change_lines(changes: &[Change]) -> String {
+    let mut output = String::new();
+    for change in changes {
+        if let Change::Value { row, column, value } = *change {
+            output.push_str(&line(row, column, state(value)));
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Label;
+    use crate::patch::{Lock, PatchCell};
+
+    #[test]
+    fn line_round_trip() {
+        assert_eq!(line(3, 12, '1'), "03:12:1\n");
+        assert_eq!(parse_line(&line(3, 12, '1')), Some((3, 12, '1')));
+        assert_eq!(parse_line("45:07:0"), Some((45, 7, '0')));
+    }
+
+    #[test]
+    fn parse_line_rejects_garbage() {
+        assert_eq!(parse_line(""), None);
+        assert_eq!(parse_line("03:12"), None);
+        assert_eq!(parse_line("03:12:1:0"), None);
+        assert_eq!(parse_line("aa:12:1"), None);
+    }
+
+    #[test]
+    fn patch_lines_covers_every_cell() {
+        let label =
+            |name: &str| Label { name: name.to_string(), description: String::new() };
+        let board = Board {
+            name:         "test".to_string(),
+            sources:      vec![label("osc"), label("lfo")],
+            destinations: vec![label("vcf")],
+        };
+        let patch =
+            Patch { cells: vec![PatchCell { row: 0, column: 1, value: 0.5 }], ..Patch::default() };
+        assert_eq!(patch_lines(&patch, &board), "00:00:0\n00:01:1\n");
+    }
+
+    #[test]
+    fn change_lines_skips_locks() {
+        let changes =
+            [Change::Locked(Lock::Row(0)), Change::Value { row: 1, column: 2, value: 0.0 }];
+        assert_eq!(change_lines(&changes), "01:02:0\n");
+    }
+}
@@ -1,3 +1,4 @@
+use std::io::{Read, Write};
 use std::time::Duration;
 
 use nannou::prelude::*;
@@ -11,6 +12,7 @@ pub struct SerialHandler {
     connected:      bool,
     print_activity: bool,
     baudrate:       u32,
+    last_error:     Option<String>,
 }
 
 impl SerialHandler {
@@ -21,38 +23,85 @@ impl SerialHandler {
             print_activity,
             port_name: port_name.to_owned(),
             baudrate,
+            last_error: None,
         }
     }
 
     pub fn print_avaliable_ports() {
-        let ports = serialport::available_ports().expect("No ports found!");
-        for p in ports {
-            println!("{}", p.port_name);
+        match serialport::available_ports() {
+            Ok(ports) =>
+                for p in ports {
+                    println!("{}", p.port_name);
+                },
+            Err(e) => println!("Failed to list ports: {}", e),
         }
     }
 
+    pub fn available_ports() -> Vec<String> {
+        serialport::available_ports()
+            .map(|ports| ports.into_iter().map(|p| p.port_name).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn port_name(&self) -> &str { &self.port_name }
+    pub fn baudrate(&self) -> u32 { self.baudrate }
+    pub fn is_connected(&self) -> bool { self.connected }
+    pub fn last_error(&self) -> Option<&str> { self.last_error.as_deref() }
+
+    // changing the port or baudrate drops the current connection
+    pub fn set_port(&mut self, port_name: &str, baudrate: u32) {
+        if port_name != self.port_name || baudrate != self.baudrate {
+            self.disconnect();
+            self.port_name = port_name.to_owned();
+            self.baudrate = baudrate;
+        }
+    }
+
+    pub fn connect(&mut self) -> Result<(), serialport::Error> {
+        match self.open_port() {
+            Ok(port) => {
+                self.port = Some(port);
+                self.connected = true;
+                self.last_error = None;
+                Ok(())
+            }
+            Err(e) => {
+                self.disconnect();
+                self.last_error = Some(e.to_string());
+                Err(e)
+            }
+        }
+    }
+
+    pub fn disconnect(&mut self) {
+        self.port = None;
+        self.connected = false;
+    }
+
     pub fn write(&mut self, ascii: &AsciiStr) {
+        if ascii.is_empty() {
+            return;
+        }
         // if self.print_activity {
         //     print!("{}", ascii);
         // }
 
-        let mut port = self.open_port();
-
-        match port {
-            Ok(mut port) => {
-                let output = ascii.as_bytes();
-                port.write(output);
-
-                let mut serial_buf: Vec<u8> = vec![0; 32];
-                let read = port.read(serial_buf.as_mut_slice());
-                if let Ok(_) = read {
-                    match String::from_utf8(serial_buf) {
-                        Ok(v) => println!("{}", v),
-                        Err(e) => panic!("Invalid UTF-8 sequence: {}", e),
-                    };
-                }
-            }
-            Err(e) => println!("Failed to open port: {}", e),
+        let port = match &mut self.port {
+            Some(port) => port,
+            None => return,
+        };
+
+        let output = ascii.as_bytes();
+        if let Err(e) = port.write_all(output) {
+            println!("Failed to write to port: {}", e);
+            self.last_error = Some(e.to_string());
+            self.disconnect();
+            return;
+        }
+
+        let mut serial_buf: Vec<u8> = vec![0; 32];
+        if let Ok(read) = port.read(serial_buf.as_mut_slice()) {
+            println!("{}", String::from_utf8_lossy(&serial_buf[..read]));
         }
     }
 
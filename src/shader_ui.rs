@@ -1,28 +1,93 @@
-use std::path::{self, Path};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{self, Path, PathBuf};
+
+use chrono::Local;
 
-use nannou::image::{DynamicImage, ImageBuffer};
 use nannou::prelude::*;
 
+use crate::board::{Board, Label};
+use crate::layout::*;
+use crate::matrix::PatchMatrix;
+use crate::patch::{Lock, Patch};
+use crate::protocol;
 use crate::sub_divide;
 use hecs::*;
 use std::iter;
 use sub_divide::SubdivideExt;
 use Orientation::*;
 const line_weight: f32 = 2.0;
+const LOCK_COLOUR: Rgb<u8> = ORANGE;
+// padding around the matrix as a share of the shortest window side
+const PADDING_RATIO: f32 = 0.11;
+pub const MAX_ZOOM: f32 = 8.0;
+// the levels the cell menu offers
+const MENU_LEVELS: [f32; 4] = [0.25, 0.5, 0.75, 1.0];
+// touchpads scroll in pixels, this many make one zoom step of a wheel notch
+const PIXELS_PER_ZOOM_STEP: f32 = 50.0;
+// the header row and column take this many cells, the space between buses a part of one
+const HEADER_WEIGHT: f32 = 4.0;
+const BUS_GAP: f32 = 0.3;
+const HIGHLIGHT_COLOUR: Rgb<u8> = YELLOW;
+// preset thumbnails, the same shape as the default window
+const THUMBNAIL_SIZE: [u32; 2] = [240, 120];
+// a finger that moves further than this is dragging, lifting it doesn't toggle
+const TOUCH_SLOP: f32 = 12.0;
+// pressing harder than this sets a level instead of toggling, a light tap reads below it
+const PRESS_START: f32 = 0.4;
 //componants
 pub struct FluffUi {
     scrolling: bool,
     cursor:    Vec2,
     world:     World,
+    // loaded once, the draw path runs twice a frame
+    font:      text::Font,
 
     screen: Rect,
 
-    // cols_rows: UVec2,
-    bounds:  Rect,
-    rows:    u32,
-    columns: u32,
+    // the patch state, the world only mirrors it for drawing and editing
+    matrix: PatchMatrix,
 
-    current_cell: Option<((usize, usize))>,
+    // cols_rows: UVec2,
+    bounds:    Rect,
+    rows:      u32,
+    columns:   u32,
+    // the part of the bounds left for cells once the pinned headers are drawn
+    grid_view: Rect,
+    // space kept free on the left of the window, for the control panel
+    inset:     f32,
+    zoom:      f32,
+    pan:       Vec2,
+    panning:   Option<Vec2>,
+
+    // the fingers on a touchscreen by id
+    touches:        HashMap<u64, TouchPoint>,
+    // the distance between two fingers the last time a pinch moved
+    pinch:          Option<f32>,
+    // the force of a touch, only in the raw window event, kept until the touch itself comes in
+    touch_force:    Option<(u64, f32)>,
+    // the level pressed on a touchpad since the button went down
+    touchpad_level: Option<f32>,
+    // the cell values from before the first level of a press, one history step when it ends
+    press_before:   Option<Vec<(Entity, f32)>>,
+
+    // keyboard cursor as (serial row, serial column), takes over focus from the mouse when set
+    current_cell:  Option<((usize, usize))>,
+    grid:          Vec<Vec<Entity>>,
+    // the vertical group holding each column of the grid
+    column_groups: Vec<Entity>,
+    stroke:        Option<PaintStroke>,
+    // each step holds the values cells had before the edit
+    history:       Vec<Vec<(Entity, f32)>>,
+    row_labels:    Vec<String>,
+    col_labels:    Vec<String>,
+
+    search:         String,
+    searching:      bool,
+    // hide the rows and columns that don't match the search, otherwise highlight the matches
+    hide_unmatched: bool,
+
+    static_layer: StaticLayer,
 
     // The texture that we will draw to.
     texture:          wgpu::Texture,
@@ -32,22 +97,21 @@ pub struct FluffUi {
     renderer:         nannou::draw::Renderer,
     // The type used to capture the texture.
     texture_capturer: wgpu::TextureCapturer,
-    // The type used to resize our texture to the window texture.
-    texture_reshaper: wgpu::TextureReshaper,
-    dynamic_image:    DynamicImage,
 }
 
 impl FluffUi {
-    pub fn new(app: &App, row_names: &Vec<&str>, col_names: &Vec<&str>) -> Self {
+    pub fn new(app: &App, board: &Board) -> Self {
+        let (row_names, col_names) = (&board.sources, &board.destinations);
+        let row_labels = col_names.iter().map(|label| label.name.clone()).collect();
+        let col_labels = row_names.iter().map(|label| label.name.clone()).collect();
+
         let path = app.assets_path().unwrap().join("fonts/Inconsolata-Regular.ttf");
         let font = text::font::from_file(path).unwrap();
 
         let screen = app.window_rect();
 
-        //downsample texture stuff
-        let texture_size = [200, 180];
-
-        let dynamic_image = DynamicImage::new_rgba8(800, 600);
+        //downsample texture stuff, used for the preset thumbnails
+        let texture_size = THUMBNAIL_SIZE;
 
         let mut world = World::new();
 
@@ -55,13 +119,14 @@ impl FluffUi {
 
         // Retrieve the wgpu device.
         let device = window.device();
+        let (width, height) = window.inner_size_pixels();
 
         // Create our custom texture.
         let sample_count = window.msaa_samples();
         let texture = wgpu::TextureBuilder::new()
             .size(texture_size)
             // Our texture will be used as the RENDER_ATTACHMENT for our `Draw` render pass.
-            // It will also be SAMPLED by the `TextureCapturer` and `TextureResizer`.
+            // It will also be SAMPLED by the `TextureCapturer`.
             .usage(wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING)
             // Use nannou's default multisampling sample count.
             .sample_count(sample_count)
@@ -79,20 +144,7 @@ impl FluffUi {
         // Create the texture capturer.
         let texture_capturer = wgpu::TextureCapturer::default();
 
-        // Create the texture reshaper.
-        let texture_view = texture.view().build();
-        let texture_sample_type = texture.sample_type();
-        let dst_format = Frame::TEXTURE_FORMAT;
-        let texture_reshaper = wgpu::TextureReshaper::new(
-            device,
-            &texture_view,
-            sample_count,
-            texture_sample_type,
-            sample_count,
-            dst_format,
-        );
-
-        let bounds = &screen.pad(100.0);
+        let bounds = &matrix_bounds(screen);
         // let bounds = bounds.first().unwrap();
 
         let default_bounds = Rect::from_w_h(10.0, 10.0);
@@ -100,63 +152,89 @@ impl FluffUi {
         let (row_count, col_count) = (row_names.len() as u32 + 1, col_names.len() as u32 + 1);
 
         let default_bounds = Bounds { shape: default_bounds, update: false };
-        let spawn_label = |name: &str, direction: Orientation| {
+        let spawn_label = |label: &Label, direction: Orientation, heading: Heading| {
             (
                 default_bounds,
                 Title {
-                    text:        name.to_string(),
+                    text:        label.name.clone(),
                     orientation: direction,
                     fill:        Rgb::from_format(WHITE),
                 },
                 Stroke { weight: line_weight, colour: Rgb::from_format(WHITE) },
                 Fill(Rgb::from_format(BLACK)),
+                Focus(false),
+                heading,
+                Description(label.description.clone()),
+                LabelText { shape: Rect::from_w_h(0.0, 0.0), size: 0 },
             )
         };
 
         // make the colum of row titles
+        // the vertical titles address the serial column, the horizontal ones the serial row
+
+        let row_titles: Vec<_> = row_names
+            .iter()
+            .enumerate()
+            .map(|(j, name)| {
+                world.spawn(spawn_label(name, Vertical, Heading { axis: Axis::Column, index: j }))
+            })
+            .collect();
+
+        let col_titles: Vec<_> = col_names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                world.spawn(spawn_label(name, Horizontal, Heading { axis: Axis::Row, index: i }))
+            })
+            .collect();
+
+        // the header column is wider than the cells, buses like `counter_x_*` are kept together
+        // with a gap before the next one
+        let mut row_names = vec![world.spawn((Spacer, Flex::weight(HEADER_WEIGHT)))];
+        for (j, row) in row_titles.iter().enumerate() {
+            if bus_gap(&board.sources, j) {
+                row_names.push(world.spawn((Spacer, Flex::weight(BUS_GAP))));
+            }
+            row_names.push(*row);
+        }
+        let row_names = system_vertical_group(&mut world, row_names, bounds);
+        world.insert_one(row_names, Flex::weight(HEADER_WEIGHT)).unwrap();
+        let mut v_groups = vec![row_names];
 
-        let row_titles: Vec<_> =
-            row_names.iter().map(|name| world.spawn(spawn_label(name, Vertical))).collect();
-
-        let col_titles: Vec<_> =
-            col_names.iter().map(|name| world.spawn(spawn_label(name, Horizontal))).collect();
-
-        let mut more_column = vec![];
+        let mut grid = vec![];
+        let mut column_groups = vec![];
 
         for (i, col) in col_titles.iter().enumerate() {
-            let mut column = vec![];
-
-            // let title = world.spawn(spawn_label(name, Horizontal));
-
-            column.push(*col);
+            if bus_gap(&board.destinations, i) {
+                v_groups.push(world.spawn((Spacer, Flex::weight(BUS_GAP))));
+            }
+            world.insert_one(*col, Flex::weight(HEADER_WEIGHT)).unwrap();
+            let mut column = vec![*col];
+            let mut cells = vec![];
 
             for (j, row) in row_titles.iter().enumerate() {
+                if bus_gap(&board.sources, j) {
+                    column.push(world.spawn((Spacer, Flex::weight(BUS_GAP))));
+                }
                 let c1: Cell = Box::new(FloatCell { val: 0.0 });
-                let c2: Cell = Box::new(BoolCell { val: false });
+                let c2: Cell = Box::new(LevelCell { val: 0.0 });
 
                 let cell = world.spawn((
                     default_bounds,
                     c2,
-                    SerialUpdate(true),
+                    SerialUpdate(false),
                     Index { row: i, column: j },
                     Focus(false),
                     Headings { row: *row, column: *col },
                     OnScroll(Box::new(|a| a + 1.0)),
                 ));
                 column.push(cell);
+                cells.push(cell);
             }
-            more_column.push(column);
-        }
-        let mut row_names = vec![world.spawn((Spacer,))];
-        row_names.extend(row_titles);
-        let row_names = system_vertical_group(&mut world, row_names, bounds);
-        let mut v_groups = vec![];
-
-        v_groups.push(row_names);
-
-        for col in more_column {
-            let v = system_vertical_group(&mut world, col, bounds);
+            grid.push(cells);
+            let v = system_vertical_group(&mut world, column, bounds);
             v_groups.push(v);
+            column_groups.push(v);
         }
 
         world.spawn((
@@ -166,144 +244,892 @@ impl FluffUi {
             NeedsRefresh,
         ));
 
+        // the first output sends every cell, so the board starts from a known state
+        let mut matrix = PatchMatrix::new(board.clone());
+        matrix.lock_defaults();
+        matrix.refresh();
+        system_pull_matrix(&mut world, &matrix);
+
         Self {
             world,
+            font,
+            matrix,
             scrolling: false,
             cursor: app.mouse.position(),
             screen,
 
             current_cell: None,
+            grid,
+            column_groups,
+            search: String::new(),
+            searching: false,
+            hide_unmatched: true,
+            stroke: None,
+            history: vec![],
+            row_labels,
+            col_labels,
             rows: row_count,
             columns: col_count,
 
             bounds: bounds.clone(),
+            grid_view: bounds.clone(),
+            inset: 0.0,
+            zoom: 1.0,
+            pan: Vec2::ZERO,
+            panning: None,
+            touches: HashMap::new(),
+            pinch: None,
+            touch_force: None,
+            touchpad_level: None,
+            press_before: None,
 
             //renderer stuff
+            static_layer: StaticLayer::new(device, [width, height]),
             texture,
             draw,
             renderer,
             texture_capturer,
-            texture_reshaper,
-            dynamic_image,
         }
     }
 
     pub fn event_handler(&mut self, app: &App, event: &WindowEvent) -> () {
+        self.handle_event(app, event);
+        self.commit();
+    }
+
+    fn handle_event(&mut self, app: &App, event: &WindowEvent) {
         match *event {
             MouseWheel(delta, phase) => {
-                let change = match delta {
-                    MouseScrollDelta::LineDelta(x, y) => vec2(x as f32, y as f32),
-                    MouseScrollDelta::PixelDelta(position) =>
-                        vec2(position.x as f32, position.y as f32),
+                let (change, steps) = match delta {
+                    MouseScrollDelta::LineDelta(x, y) => (vec2(x as f32, y as f32), y as f32),
+                    MouseScrollDelta::PixelDelta(position) => {
+                        let change = vec2(position.x as f32, position.y as f32);
+                        (change, change.y / PIXELS_PER_ZOOM_STEP)
+                    }
                 };
-                system_scroll_update(&mut self.world, change);
+                if app.keys.mods.ctrl() {
+                    if steps != 0.0 {
+                        self.zoom_at(app.mouse.position(), steps);
+                    }
+                } else {
+                    system_scroll_update(&mut self.world, change);
+                }
             }
             MousePressed(mouse) => {
-                system_button_pressed(&mut self.world, mouse);
+                self.touchpad_level = None;
+                let before = system_cell_values(&self.world);
+                if system_menu_pressed(&mut self.world) {
+                    self.push_history(before);
+                    return;
+                }
+                match mouse {
+                    MouseButton::Left if app.keys.mods.ctrl() =>
+                        self.panning = Some(app.mouse.position()),
+                    MouseButton::Left if !system_focus_momentary(&self.world) =>
+                        self.begin_stroke(app.keys.mods.shift()),
+                    MouseButton::Right => {
+                        system_button_pressed(&mut self.world, mouse);
+                        system_open_menu(&mut self.world, app.mouse.position(), self.screen);
+                    }
+                    _ => system_button_pressed(&mut self.world, mouse),
+                }
+                if mouse == MouseButton::Middle {
+                    self.push_history(before);
+                }
             }
+            MouseReleased(MouseButton::Left) => self.release_left(),
             MouseReleased(_) => (),
             Moved(_) => (),
-            KeyPressed(_) => (),
+            KeyPressed(key) => self.key_pressed(app, key),
+            KeyReleased(Key::Space) => system_release_held(&mut self.world),
             KeyReleased(_) => (),
-            ReceivedCharacter(_) => (),
-            MouseMoved(_) => (),
+            ReceivedCharacter(c) =>
+                if self.searching && !c.is_control() {
+                    self.search.push(c);
+                    self.apply_search();
+                },
+            MouseMoved(_) => self.current_cell = None,
             MouseEntered => (),
-            MouseExited => (),
-            // Resized(_) => system_resize_window(&mut self.world, app),
-            Resized(_) => (),
+            // a release outside the window may never arrive
+            MouseExited => self.release_left(),
+            // also sent after a dpi change or a fullscreen toggle
+            Resized(_) => {
+                self.screen = app.window_rect();
+                self.bounds = matrix_bounds(self.screen.pad_left(self.inset));
+                system_close_menu(&mut self.world);
+                self.apply_view();
+            }
             HoveredFile(_) => (),
             DroppedFile(_) => (),
             HoveredFileCancelled => (),
-            Touch(_) => (),
-            TouchPressure(_) => (),
+            Touch(touch) => self.touch(touch),
+            TouchPressure(pressure) =>
+                if app.mouse.buttons.left().is_down() {
+                    self.touchpad_pressed(pressure.pressure);
+                },
             Focused => (),
-            Unfocused => (),
+            Unfocused => self.release_left(),
             Closed => (),
         }
     }
 
-    pub fn draw_function(&self, draw: &Draw) {
-        let font = text::font::from_file(Path::new("assets/fonts/Inconsolata-Regular.ttf")).unwrap();
+    // taps toggle the cell under the finger, any number at once, two fingers pinch to zoom
+    fn touch(&mut self, touch: TouchEvent) {
+        let force = match self.touch_force.take() {
+            Some((id, force)) if id == touch.id => Some(force),
+            _ => None,
+        };
+        match touch.phase {
+            TouchPhase::Started => {
+                let cell = system_cell_at(&self.world, touch.position, self.grid_view);
+                if let Some((row, column)) = cell {
+                    self.current_cell = cell;
+                    system_set_focus(&mut self.world, self.grid[row][column]);
+                }
+                let point = TouchPoint {
+                    start: touch.position,
+                    position: touch.position,
+                    cell,
+                    level: None,
+                    dragged: false,
+                };
+                self.touches.insert(touch.id, point);
+                self.pinch = None;
+            }
+            TouchPhase::Moved => {
+                if let Some(point) = self.touches.get_mut(&touch.id) {
+                    point.position = touch.position;
+                    point.dragged |= point.start.distance(touch.position) > TOUCH_SLOP;
+                }
+                self.pinch_zoom();
+            }
+            TouchPhase::Ended => {
+                if let Some(point) = self.touches.remove(&touch.id) {
+                    if let (Some((row, column)), false, None) =
+                        (point.cell, point.dragged, point.level)
+                    {
+                        let value = if self.matrix.value(row, column) != 0.0 { 0.0 } else { 1.0 };
+                        self.set_cell(row, column, value);
+                    }
+                }
+                self.pinch = None;
+                self.end_press();
+            }
+            TouchPhase::Cancelled => {
+                self.touches.remove(&touch.id);
+                self.pinch = None;
+                self.end_press();
+            }
+        }
+        if let Some(force) = force {
+            self.touch_pressed(touch.id, force);
+        }
+    }
+
+    // the force comes with the raw window event, just before the touch it belongs to
+    pub fn set_touch_force(&mut self, id: u64, force: f32) { self.touch_force = Some((id, force)); }
+
+    // pressing harder raises the level of the cell, it stays at the highest level reached
+    fn touch_pressed(&mut self, id: u64, force: f32) {
+        let (cell, level) = match (self.touches.get_mut(&id), press_level(force)) {
+            (Some(point), Some(level)) if !point.dragged && point.level < Some(level) => {
+                point.level = Some(level);
+                (point.cell, level)
+            }
+            _ => return,
+        };
+        if let Some((row, column)) = cell {
+            self.press_cell(row, column, level);
+        }
+    }
 
-        system_draw_connecting_lines(&self.world, &draw);
+    // the same on a touchpad that reports pressure, for the focused cell
+    fn touchpad_pressed(&mut self, pressure: f32) {
+        let level = match press_level(pressure) {
+            Some(level) if self.touchpad_level < Some(level) => level,
+            _ => return,
+        };
+        self.touchpad_level = Some(level);
+        if let Some((row, column)) = system_focused_index(&self.world) {
+            self.press_cell(row, column, level);
+        }
+    }
 
-        system_draw_basic(&self.world, &draw);
-        system_draw_titles(&self.world, &draw, &font);
-        system_draw_value(&self.world, &draw, &font);
+    // like `set_cell`, but the levels of a press only go into the history once it ends
+    fn press_cell(&mut self, row: usize, column: usize, level: f32) {
+        self.commit();
+        if self.press_before.is_none() {
+            self.press_before = Some(system_cell_values(&self.world));
+        }
+        if self.matrix.set(row, column, level) {
+            system_pull_matrix(&mut self.world, &self.matrix);
+        }
+    }
+
+    // one step for all the presses, once no finger or touchpad is pressing any more
+    fn end_press(&mut self) {
+        let pressing = self.touches.values().any(|point| point.level.is_some());
+        if pressing || self.touchpad_level.is_some() {
+            return;
+        }
+        if let Some(before) = self.press_before.take() {
+            self.push_history(before);
+        }
+    }
+
+    // zooms around the middle of two fingers by how far they moved apart
+    fn pinch_zoom(&mut self) {
+        if self.touches.len() != 2 {
+            return;
+        }
+        let mut points = self.touches.values_mut();
+        let (a, b) = (points.next().unwrap(), points.next().unwrap());
+        a.dragged = true;
+        b.dragged = true;
+        let (distance, centre) = (a.position.distance(b.position), (a.position + b.position) / 2.0);
+        if let Some(last) = self.pinch {
+            if last > 0.0 && distance > 0.0 {
+                self.zoom_at(centre, (distance / last).ln() / 1.1f32.ln());
+            }
+        }
+        self.pinch = Some(distance);
+    }
+
+    // the grid and labels, these only change with the layout or the patch
+    fn draw_static(&self, draw: &Draw) {
+        let font = &self.font;
+
+        let grid = draw.scissor(self.grid_view);
+        system_draw_connecting_lines(&self.world, &grid);
+        system_draw_value(&self.world, &grid, font, false);
+        system_draw_locks(&self.world, &grid);
+
+        // headers stay pinned to the edges of the view, drawn over the grid
+        for orientation in [Vertical, Horizontal] {
+            let headers = draw.scissor(header_view(self.bounds, self.grid_view, &orientation));
+            system_draw_basic(&self.world, &headers, &orientation);
+            system_draw_titles(&self.world, &headers, font, &orientation);
+        }
+    }
+
+    // focus, menus and everything else that follows the mouse
+    fn draw_overlay(&self, draw: &Draw) {
+        let font = &self.font;
+
+        let grid = draw.scissor(self.grid_view);
+        system_draw_crosshair(&self.world, &grid, self.grid_view);
+        system_draw_focus_lines(&self.world, &grid);
+        system_draw_value(&self.world, &grid, font, true);
+
+        for orientation in [Vertical, Horizontal] {
+            let headers = draw.scissor(header_view(self.bounds, self.grid_view, &orientation));
+            system_draw_hovered_titles(&self.world, &headers, font, &orientation);
+        }
+        system_draw_tooltip(&self.world, &draw, font, self.screen);
+
+        system_draw_menu(&self.world, &draw, font);
+
+        if self.searching || !self.search.is_empty() {
+            let mode = if self.hide_unmatched { "filter" } else { "highlight" };
+            let cursor = if self.searching { "_" } else { "" };
+            let line = Rect::from_w_h(self.bounds.w(), 20.0)
+                .align_left_of(self.bounds)
+                .below(self.bounds)
+                .shift_y(-10.0);
+            draw.text(&format!("{}: {}{}", mode, self.search, cursor))
+                .xy(line.xy())
+                .wh(line.wh())
+                .font(font.clone())
+                .color(HIGHLIGHT_COLOUR)
+                .font_size(14)
+                .left_justify();
+        }
+
+        if let Some(PaintStroke { start, last, rectangle: true, .. }) = &self.stroke {
+            let corners = [self.grid[start.0][start.1], self.grid[last.0][last.1]]
+                .map(|id| self.world.get::<&Bounds>(id).unwrap().shape);
+            let selection = corners[0]
+                .stretch_to_point(corners[1].top_left())
+                .stretch_to_point(corners[1].bottom_right());
+            draw.rect()
+                .xy(selection.xy())
+                .wh(selection.wh())
+                .no_fill()
+                .stroke_color(PINK)
+                .stroke_weight(line_weight);
+        }
 
         // let draw = &app.draw();
     }
 
+    // draws the grid and labels into the small texture, scaled so the whole window fits
     pub fn low_rez_render(&mut self, app: &App) {
         let draw = &self.draw;
         draw.reset();
 
         draw.background().color(BLACK);
 
-        self.draw_function(draw);
+        self.draw_static(draw);
 
         let window = app.main_window();
         let device = window.device();
+        let [width, height] = self.texture.size();
+        let scale = (width as f32 / self.screen.w()).min(height as f32 / self.screen.h());
+        let view = self.texture.view().build();
         let ce_desc = wgpu::CommandEncoderDescriptor { label: Some("texture renderer") };
         let mut encoder = device.create_command_encoder(&ce_desc);
-        self.renderer.render_to_texture(device, &mut encoder, draw, &self.texture);
+        self.renderer.encode_render_pass(
+            device,
+            &mut encoder,
+            draw,
+            scale,
+            self.texture.size(),
+            &view,
+            None,
+        );
 
         window.queue().submit(Some(encoder.finish()));
     }
 
+    // the low resolution render, shown beside the preset names
+    pub fn save_thumbnail(&mut self, app: &App, path: PathBuf) {
+        self.low_rez_render(app);
+
+        let window = app.main_window();
+        let device = window.device();
+        let ce_desc = wgpu::CommandEncoderDescriptor { label: Some("thumbnail capture") };
+        let mut encoder = device.create_command_encoder(&ce_desc);
+        let snapshot = self.texture_capturer.capture(device, &mut encoder, &self.texture);
+        window.queue().submit(Some(encoder.finish()));
+        save_snapshot(snapshot, path);
+    }
+
+    // renders the matrix at the window resolution into a png named after the time it was taken
+    pub fn save_screenshot(&mut self, app: &App, dir: &Path) {
+        if let Err(e) = fs::create_dir_all(dir) {
+            println!("Failed to create {}: {}", dir.display(), e);
+            return;
+        }
+        // milliseconds, so screenshots taken in quick succession don't overwrite each other
+        let path = dir.join(Local::now().format("fluff-%Y-%m-%d-%H%M%S-%3f.png").to_string());
+
+        let window = app.main_window();
+        let device = window.device();
+        let (width, height) = window.inner_size_pixels();
+        let layer = StaticLayer::new(device, [width, height]);
+        layer.draw.background().color(BLACK);
+        self.draw_static(&layer.draw);
+        self.draw_overlay(&layer.draw);
+
+        let StaticLayer { texture, mut renderer, draw, .. } = layer;
+        let view = texture.view().build();
+        let ce_desc = wgpu::CommandEncoderDescriptor { label: Some("screenshot") };
+        let mut encoder = device.create_command_encoder(&ce_desc);
+        renderer.encode_render_pass(
+            device,
+            &mut encoder,
+            &draw,
+            window.scale_factor(),
+            texture.size(),
+            &view,
+            None,
+        );
+        let snapshot = self.texture_capturer.capture(device, &mut encoder, &texture);
+        window.queue().submit(Some(encoder.finish()));
+        save_snapshot(snapshot, path);
+    }
+
     pub fn update(&mut self, app: &App) -> () {
         self.cursor = system_snap_mouse(&self.world, app.mouse.position());
+        // don't hold back the stroke on a release that went missing
+        if self.stroke.is_some() && app.mouse.buttons.left().is_up() {
+            self.release_left();
+        }
         // self.grid.update(app);
         // let mouse = ;
 
-        system_check_focus(&mut self.world, app.mouse.position());
-        system_update_flexbox(&mut self.world);
-        self.low_rez_render(app);
+        if let Some(last) = self.panning {
+            let mouse = app.mouse.position();
+            if mouse != last {
+                self.pan += mouse - last;
+                self.panning = Some(mouse);
+                self.apply_view();
+            }
+        }
+
+        match self.current_cell {
+            Some((row, column)) => system_set_focus(&mut self.world, self.grid[row][column]),
+            None => system_check_focus(
+                &mut self.world,
+                app.mouse.position(),
+                self.bounds,
+                self.grid_view,
+            ),
+        }
+        for error in system_update_flexbox(&mut self.world) {
+            println!("Failed to lay out the matrix: {}", error);
+        }
+        self.grid_view = system_pin_headers(&mut self.world, self.bounds);
+        system_fit_labels(&mut self.world);
+
+        if let Some(stroke) = &mut self.stroke {
+            if let Some(current) = system_focused_index(&self.world) {
+                if !stroke.rectangle && current != stroke.last {
+                    for (row, column) in cells_between(stroke.last, current) {
+                        system_paint(&mut self.world, self.grid[row][column], stroke.value);
+                    }
+                }
+                stroke.last = current;
+            }
+        }
+
+        self.commit();
+        self.render_static(app);
+    }
+
+    // renders the static layer again when the layout or the patch changed since the last time
+    fn render_static(&mut self, app: &App) {
+        let window = app.main_window();
+        let device = window.device();
+        let (width, height) = window.inner_size_pixels();
+        if self.static_layer.texture.size() != [width, height] {
+            self.static_layer = StaticLayer::new(device, [width, height]);
+        }
+        if !self.static_layer.dirty && self.static_layer.revision == self.matrix.revision() {
+            return;
+        }
+
+        let draw = &self.static_layer.draw;
+        draw.reset();
+        draw.background().color(BLACK);
+        self.draw_static(draw);
+
+        let StaticLayer { texture, renderer, draw, .. } = &mut self.static_layer;
+        let view = texture.view().build();
+        let ce_desc = wgpu::CommandEncoderDescriptor { label: Some("static layer") };
+        let mut encoder = device.create_command_encoder(&ce_desc);
+        renderer.encode_render_pass(
+            device,
+            &mut encoder,
+            draw,
+            window.scale_factor(),
+            texture.size(),
+            &view,
+            None,
+        );
+        window.queue().submit(Some(encoder.finish()));
+
+        self.static_layer.dirty = false;
+        self.static_layer.revision = self.matrix.revision();
+    }
+
+    // moves the edits made in the world over to the matrix
+    fn commit(&mut self) {
+        system_commit_cells(&mut self.world, &mut self.matrix);
+        let locked = system_locks(&self.world);
+        self.matrix.set_locked(&locked);
     }
 
+    pub fn matrix(&self) -> &PatchMatrix { &self.matrix }
+
     pub fn draw(&self, app: &App, frame: &Frame) {
         let window = app.main_window();
         let screen = window.rect();
 
         let draw = &app.draw();
 
-        self.draw_function(draw);
+        draw.texture(&self.static_layer.texture).wh(screen.wh()).xy(screen.xy());
+        self.draw_overlay(draw);
 
-        // draw.texture(&self.texture).wh(screen.wh()).xy(screen.xy());
         draw.to_frame(app, frame).unwrap();
     }
-    pub fn get_serial_output(&mut self, app: &App) -> String { system_print_value(&mut self.world) }
+    // the next output sends every cell, for a board that was just connected or replugged
+    pub fn refresh(&mut self) { self.matrix.refresh() }
+
+    // changes made during a stroke are held back and sent as one batch when it ends
+    pub fn get_serial_output(&mut self, app: &App) -> String {
+        if self.stroke.is_some() {
+            return String::new();
+        }
+        protocol::change_lines(&self.matrix.take_changes())
+    }
     pub fn get_cell_values(&self) -> Vec<(f32, usize, usize)> { system_get_cell_values(&self.world) }
+
+    fn key_pressed(&mut self, app: &App, key: Key) {
+        if self.searching {
+            match key {
+                Key::Back => {
+                    self.search.pop();
+                    self.apply_search();
+                }
+                Key::Tab => {
+                    self.hide_unmatched = !self.hide_unmatched;
+                    self.apply_search();
+                }
+                Key::Return => self.searching = false,
+                Key::Escape => {
+                    self.searching = false;
+                    self.search.clear();
+                    self.apply_search();
+                }
+                _ => (),
+            }
+            return;
+        }
+
+        if app.keys.mods.ctrl() {
+            match key {
+                Key::F => self.searching = true,
+                Key::L => system_toggle_lock_focused(&mut self.world),
+                Key::Z => self.undo(),
+                Key::Key0 => self.reset_view(),
+                _ => (),
+            }
+            return;
+        }
+
+        let jump = app.keys.mods.shift();
+        match key {
+            Key::Left | Key::H => self.move_cursor(-1, 0, jump),
+            Key::Right | Key::L => self.move_cursor(1, 0, jump),
+            Key::Up | Key::K => self.move_cursor(0, -1, jump),
+            Key::Down | Key::J => self.move_cursor(0, 1, jump),
+            Key::Space if system_focus_momentary(&self.world) =>
+                system_button_pressed(&mut self.world, MouseButton::Left),
+            Key::Space => {
+                self.begin_stroke(false);
+                self.end_stroke();
+            }
+            Key::F11 => {
+                let window = app.main_window();
+                window.set_fullscreen(!window.is_fullscreen());
+            }
+            Key::Escape => {
+                system_close_menu(&mut self.world);
+                self.current_cell = None;
+            }
+            _ =>
+                if let Some(digit) = key_digit(key) {
+                    let before = system_cell_values(&self.world);
+                    system_set_focused_value(&mut self.world, digit as f32 / 9.0);
+                    self.push_history(before);
+                },
+        }
+    }
+
+    // an edit from outside the grid, the connection list for example, undone like any other
+    pub fn set_cell(&mut self, row: usize, column: usize, value: f32) {
+        self.commit();
+        let before = system_cell_values(&self.world);
+        if self.matrix.set(row, column, value) {
+            system_pull_matrix(&mut self.world, &self.matrix);
+            self.push_history(before);
+        }
+    }
+
+    // puts the keyboard cursor on a cell and pans it into view
+    pub fn jump_to(&mut self, row: usize, column: usize) {
+        self.current_cell = Some((row, column));
+        self.move_cursor(0, 0, false);
+    }
+
+    // moves the keyboard cursor, starting from the mouse focus if the keyboard wasn't in use
+    fn move_cursor(&mut self, x: i32, y: i32, jump: bool) {
+        let (row, column) = self
+            .current_cell
+            .or_else(|| system_focused_index(&self.world))
+            .unwrap_or((0, 0));
+
+        let step = |labels: &Vec<String>, from: usize, delta: i32| -> usize {
+            match (delta, jump) {
+                (0, _) => from,
+                (_, true) => group_jump(labels, from, delta > 0),
+                _ => (from as i32 + delta).clamp(0, labels.len() as i32 - 1) as usize,
+            }
+        };
+
+        // step over the cells hidden by a search, staying put if there is nothing visible
+        let mut cell = (row, column);
+        let cell = loop {
+            let next = (step(&self.row_labels, cell.0, x), step(&self.col_labels, cell.1, y));
+            if next == cell {
+                break (row, column);
+            }
+            cell = next;
+            let entity = self.world.entity(self.grid[cell.0][cell.1]).unwrap();
+            if !entity.has::<Hidden>() {
+                break cell;
+            }
+        };
+        self.current_cell = Some(cell);
+        system_set_focus(&mut self.world, self.grid[cell.0][cell.1]);
+
+        // pan the cursor back into view
+        let shape = self.world.get::<&Bounds>(self.grid[cell.0][cell.1]).unwrap().shape;
+        let view = self.grid_view;
+        let shift = vec2(
+            (view.left() - shape.left()).max(0.0) + (view.right() - shape.right()).min(0.0),
+            (view.bottom() - shape.bottom()).max(0.0) + (view.top() - shape.top()).min(0.0),
+        );
+        if shift != Vec2::ZERO {
+            self.pan += shift;
+            self.apply_view();
+        }
+    }
+
+    // filters or highlights the rows and columns whose labels contain the search text
+    pub fn apply_search(&mut self) {
+        let search = self.search.to_lowercase();
+        let matches = |labels: &Vec<String>| -> Vec<bool> {
+            let matches: Vec<_> =
+                labels.iter().map(|label| label.to_lowercase().contains(&search)).collect();
+            // an axis without any match stays as it is
+            if matches.contains(&true) {
+                matches
+            } else {
+                vec![true; labels.len()]
+            }
+        };
+        let (columns, rows) = (matches(&self.row_labels), matches(&self.col_labels));
+
+        system_filter(
+            &mut self.world,
+            &self.grid,
+            &self.column_groups,
+            &columns,
+            &rows,
+            self.hide_unmatched || search.is_empty(),
+        );
+        self.static_layer.dirty = true;
+        self.current_cell = None;
+        self.apply_view();
+    }
+
+    // zooms in `steps` tenths, keeping the point under the mouse in place
+    fn zoom_at(&mut self, mouse: Vec2, steps: f32) {
+        let zoom = (self.zoom * 1.1f32.powf(steps)).clamp(1.0, MAX_ZOOM);
+        let centre = mouse - self.bounds.xy();
+        self.pan = centre - (centre - self.pan) * zoom / self.zoom;
+        self.zoom = zoom;
+        self.apply_view();
+    }
+
+    pub fn zoom(&self) -> f32 { self.zoom }
+
+    pub fn set_zoom(&mut self, zoom: f32) {
+        let zoom = zoom.clamp(1.0, MAX_ZOOM);
+        self.pan *= zoom / self.zoom;
+        self.zoom = zoom;
+        self.apply_view();
+    }
+
+    pub fn set_inset(&mut self, inset: f32) {
+        if inset != self.inset {
+            self.inset = inset;
+            self.bounds = matrix_bounds(self.screen.pad_left(inset));
+            self.apply_view();
+        }
+    }
+
+    pub fn search(&self) -> &str { &self.search }
+    pub fn hide_unmatched(&self) -> bool { self.hide_unmatched }
+
+    pub fn set_search(&mut self, search: &str, hide_unmatched: bool) {
+        if search != self.search || hide_unmatched != self.hide_unmatched {
+            self.search = search.to_string();
+            self.hide_unmatched = hide_unmatched;
+            self.apply_search();
+        }
+    }
+
+    pub fn reset_view(&mut self) {
+        self.zoom = 1.0;
+        self.pan = Vec2::ZERO;
+        self.apply_view();
+    }
+
+    // lays the matrix out at the current zoom and pan, never leaving a gap inside the bounds
+    fn apply_view(&mut self) {
+        let limit = self.bounds.wh() * (self.zoom - 1.0) / 2.0;
+        self.pan = self.pan.clamp(-limit, limit);
+        let shape = Rect::from_xy_wh(self.bounds.xy() + self.pan, self.bounds.wh() * self.zoom);
+        system_resize_window(&mut self.world, shape);
+        self.static_layer.dirty = true;
+    }
+
+    pub fn patch(&self) -> Patch { self.matrix.patch() }
+    pub fn set_patch_info(&mut self, name: &str, notes: &str) { self.matrix.set_info(name, notes) }
+    pub fn load_patch(&mut self, patch: &Patch) {
+        self.commit();
+        let before = system_cell_values(&self.world);
+        self.matrix.apply(patch);
+        system_pull_matrix(&mut self.world, &self.matrix);
+        self.push_history(before);
+    }
+
+    // paints with the opposite of the first cell, shift paints a rectangle on release instead
+    fn begin_stroke(&mut self, rectangle: bool) {
+        let start = match system_focused_index(&self.world) {
+            Some(start) => start,
+            None => return,
+        };
+        let first = self.grid[start.0][start.1];
+        let value = if self.world.get::<&Cell>(first).unwrap().visible() { 0.0 } else { 1.0 };
+
+        let before = system_cell_values(&self.world);
+        self.stroke = Some(PaintStroke { value, start, last: start, rectangle, before });
+        if !rectangle {
+            system_paint(&mut self.world, first, value);
+        }
+    }
+
+    fn release_left(&mut self) {
+        self.panning = None;
+        self.end_stroke();
+        system_release_held(&mut self.world);
+        self.touchpad_level = None;
+        self.end_press();
+    }
+
+    fn end_stroke(&mut self) {
+        let stroke = match self.stroke.take() {
+            Some(stroke) => stroke,
+            None => return,
+        };
+        if stroke.rectangle {
+            let last = system_focused_index(&self.world).unwrap_or(stroke.last);
+            let rows = stroke.start.0.min(last.0)..=stroke.start.0.max(last.0);
+            let columns = stroke.start.1.min(last.1)..=stroke.start.1.max(last.1);
+            for row in rows {
+                for column in columns.clone() {
+                    system_paint(&mut self.world, self.grid[row][column], stroke.value);
+                }
+            }
+        }
+        self.push_history(stroke.before);
+    }
+
+    // keep the cells that changed since `before` as one undo step
+    fn push_history(&mut self, before: Vec<(Entity, f32)>) {
+        let step: Vec<_> = before
+            .into_iter()
+            .filter(|(id, value)| self.world.get::<&Cell>(*id).unwrap().get_value() != *value)
+            .collect();
+        if !step.is_empty() {
+            self.history.push(step);
+        }
+    }
+
+    pub fn undo(&mut self) {
+        if let Some(step) = self.history.pop() {
+            for (id, value) in step {
+                system_paint(&mut self.world, id, value);
+            }
+        }
+    }
 }
 
-//componants-------------------------------------
-#[derive(Debug, Clone, Copy)]
+// the grid and labels rendered into a texture and reused until the layout or the patch changes
+struct StaticLayer {
+    texture:  wgpu::Texture,
+    renderer: nannou::draw::Renderer,
+    draw:     nannou::Draw,
+    dirty:    bool,
+    // the matrix revision it was rendered at
+    revision: u64,
+}
 
-struct Bounds {
-    shape:  Rect,
-    update: bool,
+impl StaticLayer {
+    fn new(device: &wgpu::Device, size: [u32; 2]) -> Self {
+        let texture = wgpu::TextureBuilder::new()
+            .size(size)
+            .usage(wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING)
+            // sampled when drawn to the window, so no multisampling
+            .sample_count(1)
+            .format(wgpu::TextureFormat::Rgba16Float)
+            .build(device);
+        let renderer = nannou::draw::RendererBuilder::new()
+            .build_from_texture_descriptor(device, texture.descriptor());
+        StaticLayer { texture, renderer, draw: nannou::Draw::new(), dirty: true, revision: 0 }
+    }
 }
-struct NeedsRefresh;
-enum Orientation {
-    Horizontal,
-    Vertical,
+
+// the png is written once the gpu has finished with the capture, a frame or so later
+fn save_snapshot(snapshot: wgpu::TextureSnapshot, path: PathBuf) {
+    let read = snapshot.read(move |result| match result {
+        Ok(image) => match image.to_owned().save(&path) {
+            Ok(_) => println!("saved {}", path.display()),
+            Err(e) => println!("Failed to save {}: {}", path.display(), e),
+        },
+        Err(e) => println!("Failed to read {}: {}", path.display(), e),
+    });
+    if let Err(e) = read {
+        println!("Failed to capture {}: {}", path.display(), e);
+    }
 }
+
+// a finger on the screen
+struct TouchPoint {
+    start:    Vec2,
+    position: Vec2,
+    cell:     Option<(usize, usize)>,
+    // the highest level pressed, none when the screen doesn't report force or it was a light tap
+    level:    Option<f32>,
+    // moved too far or pinched, so lifting it doesn't toggle
+    dragged:  bool,
+}
+
+// a firm press in steps of the number keys, none for a light one
+fn press_level(force: f32) -> Option<f32> {
+    let level = ((force - PRESS_START) / (1.0 - PRESS_START) * 9.0).round() / 9.0;
+    (level > 0.0).then(|| level.min(1.0))
+}
+
+struct PaintStroke {
+    value:     f32,
+    start:     (usize, usize),
+    last:      (usize, usize),
+    rectangle: bool,
+    before:    Vec<(Entity, f32)>,
+}
+
+// the cells on a line between two grid positions, not including the start
+fn cells_between(from: (usize, usize), to: (usize, usize)) -> Vec<(usize, usize)> {
+    let delta = vec2(to.0 as f32 - from.0 as f32, to.1 as f32 - from.1 as f32);
+    let steps = delta.x.abs().max(delta.y.abs()) as usize;
+    (1..=steps)
+        .map(|step| {
+            let position = vec2(from.0 as f32, from.1 as f32) + delta * step as f32 / steps as f32;
+            (position.x.round() as usize, position.y.round() as usize)
+        })
+        .collect()
+}
+
+//componants-------------------------------------
 struct Title {
     text:        String,
     orientation: Orientation,
     fill:        nannou::color::rgb::Rgb,
 }
+// the font size a title was fitted to, worked out again only when its bounds change, `draw.text`
+// still lays out the glyphs, but only on a static layer redraw and for the two hovered titles
+struct LabelText {
+    shape: Rect,
+    size:  u32,
+}
 
 pub struct Fill(nannou::color::rgb::Rgb);
 
 type Cell = Box<dyn CellType + Send + Sync>;
 
+// the cell was edited and the matrix hasn't been told yet
 pub struct SerialUpdate(bool);
 
 pub trait CellType {
     fn visible(&self) -> bool;
     fn as_string(&self) -> String;
     fn get_value(&self) -> f32;
+    fn set_value(&mut self, val: f32);
     fn scoll_update(&mut self, change: Vec2) {}
     fn bool_ascii(&self) -> char { '0' }
     fn clicked_left(&mut self) {}
@@ -319,9 +1145,23 @@ impl CellType for FloatCell {
     fn visible(&self) -> bool { self.val != 0.0 }
     fn as_string(&self) -> String { format!("{:.2}", self.val) }
     fn get_value(&self) -> f32 { self.val }
+    fn set_value(&mut self, val: f32) { self.val = val }
     fn scoll_update(&mut self, change: Vec2) { self.val += change.y }
     fn clicked_left(&mut self) { self.val = 0.0 }
 }
+// toggles like a BoolCell, but keeps the levels set by the number keys, the menu and pressure
+#[derive(Debug, Clone, Copy)]
+pub struct LevelCell {
+    val: f32,
+}
+impl CellType for LevelCell {
+    fn visible(&self) -> bool { self.val != 0.0 }
+    fn as_string(&self) -> String { format!("{:.2}", self.val) }
+    fn get_value(&self) -> f32 { self.val }
+    fn set_value(&mut self, val: f32) { self.val = val }
+    fn clicked_left(&mut self) { self.val = if self.val != 0.0 { 0.0 } else { 1.0 } }
+    fn bool_ascii(&self) -> char { protocol::state(self.val) }
+}
 #[derive(Debug, Clone, Copy)]
 
 pub struct BoolCell {
@@ -337,6 +1177,7 @@ impl CellType for BoolCell {
             0.0
         }
     }
+    fn set_value(&mut self, val: f32) { self.val = val != 0.0 }
     fn clicked_left(&mut self) { self.val = !self.val }
 
     fn bool_ascii(&self) -> char {
@@ -356,16 +1197,6 @@ struct Stroke {
 }
 struct Focus(bool);
 
-struct Group {
-    children:   Vec<Entity>,
-    orentation: Orientation,
-}
-
-impl Group {
-    fn AddChild(&mut self, entity: Entity) { self.children.push(entity); }
-    fn AddChildren(&mut self, entity: &Vec<Entity>) { self.children.extend(entity); }
-}
-
 struct OnScroll(Box<dyn Fn(f32) -> f32 + Send + Sync>);
 
 struct Index {
@@ -377,36 +1208,59 @@ struct Headings {
     column: Entity,
 }
 
-struct WindowRect;
 
-pub fn system_resize_window(world: &mut World, app: &App) {
-    let screen = app.window_rect();
-    let rect = screen.pad(100.0);
+// marks a cell, or a whole row or column when on a title, as protected from edits
+struct Locked;
+struct Highlight;
+// momentary cells are only on while the button is held
+struct Momentary;
+struct Held;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MenuAction {
+    SetLevel(f32),
+    Lock,
+    Momentary,
+    ClearRow,
+    ClearColumn,
+    Solo,
+}
+struct MenuItem {
+    action: MenuAction,
+    target: Entity,
+    label:  String,
+}
 
-    let mut id_group = vec![];
-    for (id, (window, mut bounds)) in &mut world.query::<(&WindowRect, &mut Bounds)>() {
-        bounds.shape = rect;
-        bounds.update = true;
-        id_group.push(id);
-    }
-    for id in id_group {
-        world.insert_one(id, NeedsRefresh);
-    }
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Axis {
+    Row,
+    Column,
+}
+struct Heading {
+    axis:  Axis,
+    index: usize,
 }
-pub fn system_vertical_group(world: &mut World, entitys: Vec<Entity>, rect: &Rect) -> Entity {
-    world.spawn((
-        Group { children: entitys, orentation: Orientation::Vertical },
-        Bounds { shape: rect.clone(), update: true },
-        // NeedsRefresh,
-    ))
+struct Description(String);
+
+fn is_locked(world: &World, id: Entity, headings: &Headings) -> bool {
+    [id, headings.row, headings.column]
+        .iter()
+        .any(|id| world.entity(*id).is_ok_and(|entity| entity.has::<Locked>()))
 }
-pub fn system_horizontal_group(world: &mut World, entitys: Vec<Entity>, rect: &Rect) -> Entity {
-    world.spawn((
-        Group { children: entitys, orentation: Orientation::Horizontal },
-        Bounds { shape: rect.clone(), update: true },
-        NeedsRefresh,
-    ))
+
+fn matrix_bounds(screen: Rect) -> Rect { screen.pad(screen.w().min(screen.h()) * PADDING_RATIO) }
+
+// scale label text with the thin side of its box so it fits on small and large screens alike
+fn font_size(thickness: f32, scale: f32) -> u32 { (thickness * scale).clamp(5.0, 24.0) as u32 }
+
+// the strip a header row or column is drawn and focused in
+fn header_view(bounds: Rect, grid_view: Rect, orientation: &Orientation) -> Rect {
+    match orientation {
+        Orientation::Vertical => Rect { x: bounds.x, y: grid_view.y },
+        Orientation::Horizontal => Rect { x: grid_view.x, y: bounds.y },
+    }
 }
+
 //systems -------------------------------------
 pub fn system_snap_mouse(world: &World, mouse_position: Vec2) -> Vec2 {
     let mut pos = vec2(0.0, 0.0);
@@ -418,15 +1272,34 @@ pub fn system_snap_mouse(world: &World, mouse_position: Vec2) -> Vec2 {
     }
     pos
 }
-pub fn system_draw_titles(world: &World, draw: &Draw, font: &text::Font) {
-    for (id, (title, bounds)) in &mut world.query::<(&Title, &Bounds)>() {
+pub fn system_fit_labels(world: &mut World) {
+    for (_, (title, bounds, label)) in &mut world.query::<(&Title, &Bounds, &mut LabelText)>() {
+        if label.shape != bounds.shape {
+            let thickness = match title.orientation {
+                Orientation::Horizontal => bounds.shape.w(),
+                Orientation::Vertical => bounds.shape.h(),
+            };
+            label.shape = bounds.shape;
+            label.size = font_size(thickness, 0.8);
+        }
+    }
+}
+
+pub fn system_draw_titles(world: &World, draw: &Draw, font: &text::Font, orientation: &Orientation) {
+    for (id, (title, bounds, label, highlight)) in &mut world
+        .query::<(&Title, &Bounds, &LabelText, Option<&Highlight>)>()
+        .without::<&Hidden>()
+    {
+        if title.orientation != *orientation {
+            continue;
+        }
         let text = draw
             .text(&title.text)
             .xy(bounds.shape.xy())
             .font(font.clone())
-            .color(title.fill)
+            .color(if highlight.is_some() { Rgb::from_format(HIGHLIGHT_COLOUR) } else { title.fill })
             .no_line_wrap()
-            .font_size(12)
+            .font_size(label.size)
             .wh(bounds.shape.wh());
         match title.orientation {
             Orientation::Horizontal => text.rotate(TAU / 4.0).left_justify(),
@@ -435,9 +1308,10 @@ pub fn system_draw_titles(world: &World, draw: &Draw, font: &text::Font) {
     }
 }
 pub fn system_scroll_update(world: &mut World, change: Vec2) {
-    for (id, (value, focus, update)) in &mut world.query::<(&mut Cell, &Focus, &mut SerialUpdate)>()
+    for (id, (value, focus, update, headings)) in
+        &mut world.query::<(&mut Cell, &Focus, &mut SerialUpdate, &Headings)>()
     {
-        if focus.0 {
+        if focus.0 && !is_locked(world, id, headings) {
             value.scoll_update(change);
             update.0 = true;
         }
@@ -445,22 +1319,172 @@ pub fn system_scroll_update(world: &mut World, change: Vec2) {
 }
 
 pub fn system_button_pressed(world: &mut World, mouse: MouseButton) {
-    for (id, (cell, focus, update)) in &mut world.query::<(&mut Cell, &Focus, &mut SerialUpdate)>() {
-        if focus.0 {
+    let mut held = vec![];
+    for (id, (cell, focus, update, headings)) in
+        &mut world.query::<(&mut Cell, &Focus, &mut SerialUpdate, &Headings)>()
+    {
+        if focus.0 && !is_locked(world, id, headings) {
+            let momentary = world.entity(id).is_ok_and(|entity| entity.has::<Momentary>());
             match mouse {
+                MouseButton::Left if momentary => {
+                    cell.set_value(1.0);
+                    held.push(id);
+                }
                 MouseButton::Left => cell.clicked_left(),
-                MouseButton::Right => todo!(),
-                MouseButton::Middle => todo!(),
-                MouseButton::Other(_) => todo!(),
+                MouseButton::Right => cell.clicked_right(),
+                MouseButton::Middle => cell.set_value(0.0),
+                MouseButton::Other(_) => (),
             }
             update.0 = true;
         }
     }
+    for id in held {
+        world.insert_one(id, Held).unwrap();
+    }
+}
+
+pub fn system_release_held(world: &mut World) {
+    let mut released = vec![];
+    for (id, (cell, update)) in &mut world.query::<(&mut Cell, &mut SerialUpdate)>().with::<&Held>() {
+        cell.set_value(0.0);
+        update.0 = true;
+        released.push(id);
+    }
+    for id in released {
+        world.remove_one::<Held>(id).unwrap();
+    }
+}
+
+// spawns a context menu for the focused cell below the mouse
+pub fn system_open_menu(world: &mut World, position: Vec2, screen: Rect) {
+    system_close_menu(world);
+
+    let target = match world.query::<(&Cell, &Focus)>().iter().find(|(_, (_, focus))| focus.0) {
+        Some((id, _)) => id,
+        None => return,
+    };
+    let entity = world.entity(target).unwrap();
+    let (locked, momentary) = (entity.has::<Locked>(), entity.has::<Momentary>());
+
+    // the level the cell is at is marked
+    let value = entity.get::<&Cell>().map_or(0.0, |cell| cell.get_value());
+    let mut items: Vec<_> = MENU_LEVELS
+        .iter()
+        .map(|level| {
+            let mark = if *level == value { " *" } else { "" };
+            (MenuAction::SetLevel(*level), format!("level {:.2}{}", level, mark))
+        })
+        .collect();
+    items.extend([
+        (MenuAction::Lock, if locked { "unlock" } else { "lock" }.to_string()),
+        (MenuAction::Momentary, format!("momentary {}", if momentary { "off" } else { "on" })),
+        (MenuAction::ClearRow, "clear row".to_string()),
+        (MenuAction::ClearColumn, "clear column".to_string()),
+        (MenuAction::Solo, "solo".to_string()),
+    ]);
+
+    let (w, h) = (120.0, 20.0 * items.len() as f32);
+    let menu = Rect::from_x_y_w_h(position.x + w / 2.0, position.y - h / 2.0, w, h);
+    // keep the menu on screen
+    let menu = menu.shift_y((screen.bottom() - menu.bottom()).max(0.0));
+    let menu = menu.shift_x((screen.right() - menu.right()).min(0.0));
+    let shapes = menu.divide_rows(items.len() as u32);
+
+    for ((action, label), shape) in items.into_iter().zip(shapes) {
+        world.spawn((
+            Bounds { shape, update: false },
+            Focus(false),
+            MenuItem { action, target, label },
+        ));
+    }
+}
+
+pub fn system_close_menu(world: &mut World) {
+    let items: Vec<_> = world.query::<&MenuItem>().iter().map(|(id, _)| id).collect();
+    for id in items {
+        world.despawn(id).unwrap();
+    }
+}
+
+// runs the focused menu item and closes the menu, returns false when no menu was open
+pub fn system_menu_pressed(world: &mut World) -> bool {
+    let mut open = false;
+    let mut chosen = None;
+    for (id, (item, focus)) in &mut world.query::<(&MenuItem, &Focus)>() {
+        open = true;
+        if focus.0 {
+            chosen = Some((item.target, item.action));
+        }
+    }
+    if let Some((target, action)) = chosen {
+        system_menu_action(world, target, action);
+    }
+    system_close_menu(world);
+    open
+}
+
+pub fn system_menu_action(world: &mut World, target: Entity, action: MenuAction) {
+    let (row, column) = {
+        let headings = world.get::<&Headings>(target).unwrap();
+        (headings.row, headings.column)
+    };
+    match action {
+        MenuAction::Lock =>
+            if world.remove_one::<Locked>(target).is_err() {
+                world.insert_one(target, Locked).unwrap();
+            },
+        MenuAction::Momentary =>
+            if world.remove_one::<Momentary>(target).is_err() {
+                world.insert_one(target, Momentary).unwrap();
+            },
+        _ => (),
+    }
+
+    for (id, (cell, update, headings)) in
+        &mut world.query::<(&mut Cell, &mut SerialUpdate, &Headings)>()
+    {
+        if is_locked(world, id, headings) {
+            continue;
+        }
+        let same_row = headings.row == row;
+        let same_column = headings.column == column;
+        let value = match action {
+            MenuAction::SetLevel(level) if id == target => level,
+            MenuAction::ClearRow if same_row => 0.0,
+            MenuAction::ClearColumn if same_column => 0.0,
+            MenuAction::Solo if id == target => 1.0,
+            MenuAction::Solo if same_row || same_column => 0.0,
+            _ => continue,
+        };
+        if cell.get_value() != value {
+            cell.set_value(value);
+            update.0 = true;
+        }
+    }
+}
+
+pub fn system_draw_menu(world: &World, draw: &Draw, font: &text::Font) {
+    for (id, (item, bounds, focus)) in &mut world.query::<(&MenuItem, &Bounds, &Focus)>() {
+        let color = if focus.0 { PINK } else { WHITE };
+        draw.rect()
+            .xy(bounds.shape.xy())
+            .wh(bounds.shape.wh())
+            .color(BLACK)
+            .stroke_color(color)
+            .stroke_weight(line_weight / 2.0);
+        draw.text(&item.label)
+            .xy(bounds.shape.xy())
+            .wh(bounds.shape.pad_left(6.0).wh())
+            .font(font.clone())
+            .color(color)
+            .font_size(12)
+            .left_justify();
+    }
 }
 
 pub fn system_draw_connecting_lines(world: &World, draw: &Draw) {
     for (id, (value, focus, headings, bounds)) in
-        &mut world.query::<(&mut Cell, &Focus, &Headings, &Bounds)>()
+        &mut world.query::<(&mut Cell, &Focus, &Headings, &Bounds)>().without::<&Hidden>()
     {
         if value.visible() {
             let (mut x, mut y) = (
@@ -480,9 +1504,10 @@ pub fn system_draw_connecting_lines(world: &World, draw: &Draw) {
                 .weight(line_weight);
         }
     }
-
+}
+pub fn system_draw_focus_lines(world: &World, draw: &Draw) {
     for (id, (value, focus, headings, bounds)) in
-        &mut world.query::<(&mut Cell, &Focus, &Headings, &Bounds)>()
+        &mut world.query::<(&mut Cell, &Focus, &Headings, &Bounds)>().without::<&Hidden>()
     {
         if focus.0 {
             let (mut x, mut y) = (
@@ -504,29 +1529,18 @@ pub fn system_draw_connecting_lines(world: &World, draw: &Draw) {
         }
     }
 }
-pub fn system_print_value(world: &World) -> String {
-    let mut serial_output = String::new();
-    for (id, (cell, index, update)) in &mut world.query::<(&Cell, &Index, &mut SerialUpdate)>() {
-        if update.0 {
-            serial_output.push_str(&format!(
-                "{:02}:{:02}:{}",
-                index.row,
-                index.column,
-                cell.bool_ascii()
-            ));
-            serial_output.push_str("\n");
-
-            update.0 = false;
+// draws either the focused cell or all the others
+// the static layer draws every cell whatever the focus, the overlay draws the focused one over it
+pub fn system_draw_value(world: &World, draw: &Draw, font: &text::Font, focused: bool) {
+    for (id, (cell, bounds, focus)) in
+        &mut world.query::<(&Cell, &Bounds, &Focus)>().without::<&Hidden>()
+    {
+        if focused && !focus.0 {
+            continue;
         }
-    }
-
-    serial_output
-}
-pub fn system_draw_value(world: &World, draw: &Draw, font: &text::Font) {
-    for (id, (cell, bounds, focus)) in &mut world.query::<(&Cell, &Bounds, &Focus)>() {
-        let color = if focus.0 { PINK } else { GRAY };
+        let color = if focused { PINK } else { GRAY };
 
-        if cell.visible() || focus.0 {
+        if cell.visible() || focused {
             draw.ellipse()
                 .xy(bounds.shape.xy())
                 .radius(bounds.shape.w_h().0 / 2.0)
@@ -537,30 +1551,80 @@ pub fn system_draw_value(world: &World, draw: &Draw, font: &text::Font) {
                 .font(font.clone())
                 .xy(bounds.shape.xy())
                 .color(color)
-                .font_size(12)
+                .font_size(font_size(bounds.shape.w().min(bounds.shape.h()), 0.8))
                 .wh(bounds.shape.wh());
         }
     }
 }
-pub fn system_draw_basic(world: &World, draw: &Draw) {
-    for (id, (stroke, bounds)) in &mut world.query::<(&Stroke, &Bounds)>() {
+pub fn system_draw_basic(world: &World, draw: &Draw, orientation: &Orientation) {
+    for (id, (stroke, bounds, title, locked)) in
+        &mut world.query::<(&Stroke, &Bounds, &Title, Option<&Locked>)>().without::<&Hidden>()
+    {
+        if title.orientation != *orientation {
+            continue;
+        }
         draw.rect()
             .xy(bounds.shape.xy())
             .wh(bounds.shape.wh())
             .no_fill()
-            .stroke_color(stroke.colour)
+            .stroke_color(if locked.is_some() { LOCK_COLOUR } else { stroke.colour })
             .stroke_weight(stroke.weight);
     }
-    for (id, (fill, bounds)) in &mut world.query::<(&Fill, &Bounds)>() {
-        draw.rect().xy(bounds.shape.xy()).wh(bounds.shape.wh()).color(fill.0);
+    for (id, (fill, bounds, title)) in
+        &mut world.query::<(&Fill, &Bounds, &Title)>().without::<&Hidden>()
+    {
+        if title.orientation == *orientation {
+            draw.rect().xy(bounds.shape.xy()).wh(bounds.shape.wh()).color(fill.0);
+        }
     }
 }
 
-pub fn system_check_focus(world: &mut World, mouse: Vec2) {
-    for (id, (bounds, focus)) in &mut world.query::<(&Bounds, &mut Focus)>() {
-        let intersect = bounds.shape.contains(mouse);
-        focus.0 = intersect;
+// only the visible part of cells and headers can take focus
+pub fn system_check_focus(world: &mut World, mouse: Vec2, bounds: Rect, grid_view: Rect) {
+    for (id, (shape, focus, title, index, hidden)) in
+        &mut world.query::<(&Bounds, &mut Focus, Option<&Title>, Option<&Index>, Option<&Hidden>)>()
+    {
+        let visible = hidden.is_none()
+            && match (title, index) {
+                (Some(title), _) =>
+                    header_view(bounds, grid_view, &title.orientation).contains(mouse),
+                (_, Some(_)) => grid_view.contains(mouse),
+                _ => true,
+            };
+        focus.0 = visible && shape.shape.contains(mouse);
+    }
+}
+
+// the visible cell under a point, as (serial row, serial column)
+pub fn system_cell_at(world: &World, position: Vec2, grid_view: Rect) -> Option<(usize, usize)> {
+    if !grid_view.contains(position) {
+        return None;
     }
+    world
+        .query::<(&Bounds, &Index)>()
+        .without::<&Hidden>()
+        .iter()
+        .find(|(_, (bounds, _))| bounds.shape.contains(position))
+        .map(|(_, (_, index))| (index.row, index.column))
+}
+
+// keeps the row headers on the left and the column headers on the top edge of the bounds,
+// returns the space left over for the grid
+pub fn system_pin_headers(world: &mut World, bounds: Rect) -> Rect {
+    let (mut left, mut top) = (bounds.left(), bounds.top());
+    for (id, (title, shape)) in &mut world.query::<(&Title, &mut Bounds)>().without::<&Hidden>() {
+        match title.orientation {
+            Orientation::Vertical => {
+                shape.shape = shape.shape.shift_x((bounds.left() - shape.shape.left()).max(0.0));
+                left = left.max(shape.shape.right());
+            }
+            Orientation::Horizontal => {
+                shape.shape = shape.shape.shift_y((bounds.top() - shape.shape.top()).min(0.0));
+                top = top.min(shape.shape.bottom());
+            }
+        }
+    }
+    Rect::from_corners(pt2(left, top), bounds.bottom_right())
 }
 
 pub fn system_get_cell_values(world: &World) -> Vec<(f32, usize, usize)> {
@@ -570,48 +1634,343 @@ pub fn system_get_cell_values(world: &World) -> Vec<(f32, usize, usize)> {
     }
     v
 }
-pub fn system_update_flexbox(world: &mut World) {
-    let mut refresh_loop_counter = 0;
-    loop {
-        let mut refresh_completed = vec![];
-        let mut refresh_needed = vec![];
-        //borrow the first entity to need a refresh, only look at one at a time
-        if refresh_loop_counter > 1000 {
-            panic!("flexbox got caught in a feeback loop");
-        } else if let Some(first_entity) =
-            world.query::<(&Bounds, &Group, &NeedsRefresh)>().iter().next()
-        {
-            let (id, (bounds, flexbox, _)) = first_entity;
-            refresh_completed.push(id);
-
-            let sub_devisions = match flexbox.orentation {
-                Orientation::Horizontal =>
-                    bounds.shape.divide_columns(flexbox.children.len() as u32),
-                Orientation::Vertical => bounds.shape.divide_rows(flexbox.children.len() as u32),
-            };
+fn key_digit(key: Key) -> Option<u32> {
+    let digit = match key {
+        Key::Key0 | Key::Numpad0 => 0,
+        Key::Key1 | Key::Numpad1 => 1,
+        Key::Key2 | Key::Numpad2 => 2,
+        Key::Key3 | Key::Numpad3 => 3,
+        Key::Key4 | Key::Numpad4 => 4,
+        Key::Key5 | Key::Numpad5 => 5,
+        Key::Key6 | Key::Numpad6 => 6,
+        Key::Key7 | Key::Numpad7 => 7,
+        Key::Key8 | Key::Numpad8 => 8,
+        Key::Key9 | Key::Numpad9 => 9,
+        _ => return None,
+    };
+    Some(digit)
+}
 
-            for (child_id, new_bounds) in flexbox.children.iter().zip(sub_devisions) {
-                refresh_needed.push(*child_id);
-                // *world.insert(*child, ());
-                // Bounds { shape: todo!(), update: todo!()
-                let mut child_bounds = world.query_one::<(&mut Bounds)>(*child_id).unwrap();
+// labels like `invert_x_3` belong to the group `invert_x`
+fn label_group(label: &str) -> &str { label.rsplit_once('_').map_or(label, |(group, _)| group) }
 
-                if let Some(child_bounds) = child_bounds.get() {
-                    child_bounds.shape = new_bounds;
-                }
+// true when the label starts a new group, so it gets a gap before it
+fn bus_gap(labels: &[Label], i: usize) -> bool {
+    i > 0 && label_group(&labels[i].name) != label_group(&labels[i - 1].name)
+}
+
+// index of the first label of the next group, or of the current/previous group going backwards
+fn group_jump(labels: &Vec<String>, from: usize, forward: bool) -> usize {
+    let group = |i: usize| label_group(&labels[i]);
+    let mut i = from;
+    if forward {
+        while i + 1 < labels.len() && group(i) == group(from) {
+            i += 1;
+        }
+        i
+    } else {
+        if i > 0 && group(i - 1) == group(i) {
+            while i > 0 && group(i - 1) == group(from) {
+                i -= 1;
             }
+            return i;
+        }
+        i = i.saturating_sub(1);
+        while i > 0 && group(i - 1) == group(from.saturating_sub(1)) {
+            i -= 1;
+        }
+        i
+    }
+}
+
+pub fn system_cell_values(world: &World) -> Vec<(Entity, f32)> {
+    world.query::<&Cell>().iter().map(|(id, cell)| (id, cell.get_value())).collect()
+}
+
+pub fn system_paint(world: &mut World, entity: Entity, value: f32) {
+    let mut query = world.query_one::<(&mut Cell, &mut SerialUpdate, &Headings)>(entity).unwrap();
+    if let Some((cell, update, headings)) = query.get() {
+        let hidden = world.entity(entity).is_ok_and(|entity| entity.has::<Hidden>());
+        if !hidden && !is_locked(world, entity, headings) && cell.get_value() != value {
+            cell.set_value(value);
+            update.0 = true;
+        }
+    }
+}
+
+pub fn system_focus_momentary(world: &World) -> bool {
+    world.query::<&Focus>().with::<&Momentary>().iter().any(|(_, focus)| focus.0)
+}
+
+pub fn system_set_focus(world: &mut World, entity: Entity) {
+    for (id, focus) in &mut world.query::<&mut Focus>() {
+        focus.0 = id == entity;
+    }
+}
+
+pub fn system_focused_index(world: &World) -> Option<(usize, usize)> {
+    world
+        .query::<(&Index, &Focus)>()
+        .iter()
+        .find(|(_, (_, focus))| focus.0)
+        .map(|(_, (index, _))| (index.row, index.column))
+}
+
+pub fn system_set_focused_value(world: &mut World, value: f32) {
+    for (id, (cell, focus, update, headings)) in
+        &mut world.query::<(&mut Cell, &Focus, &mut SerialUpdate, &Headings)>()
+    {
+        if focus.0 && !is_locked(world, id, headings) {
+            cell.set_value(value);
+            update.0 = true;
+        }
+    }
+}
+
+pub fn system_toggle_lock_focused(world: &mut World) {
+    let focused: Vec<_> =
+        world.query::<&Focus>().iter().filter(|(_, focus)| focus.0).map(|(id, _)| id).collect();
+
+    for id in focused {
+        if world.remove_one::<Locked>(id).is_err() {
+            world.insert_one(id, Locked).unwrap();
+        }
+    }
+}
+
+pub fn system_draw_locks(world: &World, draw: &Draw) {
+    for (id, (headings, bounds)) in &mut world.query::<(&Headings, &Bounds)>().without::<&Hidden>() {
+        if is_locked(world, id, headings) {
+            let size = bounds.shape.w().min(bounds.shape.h()) / 4.0;
+            let corner = bounds.shape.top_right() - vec2(size, size) / 2.0;
+            draw.rect().xy(corner).w_h(size, size).color(LOCK_COLOUR);
+        }
+    }
+}
+
+fn heading_lock(heading: &Heading) -> Lock {
+    match heading.axis {
+        Axis::Row => Lock::Row(heading.index),
+        Axis::Column => Lock::Column(heading.index),
+    }
+}
+
+pub fn system_locks(world: &World) -> Vec<Lock> {
+    let mut locked = vec![];
+    for (_, index) in &mut world.query::<&Index>().with::<&Locked>() {
+        locked.push(Lock::Cell { row: index.row, column: index.column });
+    }
+    for (_, heading) in &mut world.query::<&Heading>().with::<&Locked>() {
+        locked.push(heading_lock(heading));
+    }
+    locked
+}
+
+// edited cells go to the matrix, the ones it refuses get its value back
+pub fn system_commit_cells(world: &mut World, matrix: &mut PatchMatrix) {
+    for (_, (cell, index, update)) in &mut world.query::<(&mut Cell, &Index, &mut SerialUpdate)>() {
+        if update.0 {
+            if !matrix.set(index.row, index.column, cell.get_value()) {
+                cell.set_value(matrix.value(index.row, index.column));
+            }
+            update.0 = false;
+        }
+    }
+}
+
+// brings the cells and locks in line with the matrix after it changed outside the ui
+pub fn system_pull_matrix(world: &mut World, matrix: &PatchMatrix) {
+    for (_, (cell, index)) in &mut world.query::<(&mut Cell, &Index)>() {
+        cell.set_value(matrix.value(index.row, index.column));
+    }
+
+    let mut locks = vec![];
+    for (id, index) in &mut world.query::<&Index>() {
+        locks.push((id, Lock::Cell { row: index.row, column: index.column }));
+    }
+    for (id, heading) in &mut world.query::<&Heading>() {
+        locks.push((id, heading_lock(heading)));
+    }
+    for (id, lock) in locks {
+        if matrix.locked().contains(&lock) {
+            world.insert_one(id, Locked).unwrap();
         } else {
-            return;
+            world.remove_one::<Locked>(id).ok();
         }
+    }
+}
 
-        for id in refresh_completed {
-            world.remove_one::<NeedsRefresh>(id);
+// `visible_columns[i]` and `visible_rows[j]` say if column i and row j of the grid match the
+// search, with `hide` the rest collapses, otherwise the matching titles are highlighted
+pub fn system_filter(
+    world: &mut World,
+    grid: &Vec<Vec<Entity>>,
+    column_groups: &Vec<Entity>,
+    visible_columns: &Vec<bool>,
+    visible_rows: &Vec<bool>,
+    hide: bool,
+) {
+    let reset: Vec<_> = world.query::<&Bounds>().iter().map(|(id, _)| id).collect();
+    for id in reset {
+        world.remove_one::<Hidden>(id).ok();
+        world.remove_one::<Highlight>(id).ok();
+    }
+
+    let mut hidden = vec![];
+    let mut highlighted = vec![];
+    for (i, column) in grid.iter().enumerate() {
+        let column_title = world.get::<&Headings>(column[0]).unwrap().column;
+        match (visible_columns[i], hide) {
+            (false, true) => {
+                hidden.push(column_groups[i]);
+                hidden.push(column_title);
+            }
+            (true, false) if visible_columns.contains(&false) => highlighted.push(column_title),
+            _ => (),
         }
+        for (j, cell) in column.iter().enumerate() {
+            if hide && !(visible_columns[i] && visible_rows[j]) {
+                hidden.push(*cell);
+            }
+        }
+    }
+    for (j, cell) in grid[0].iter().enumerate() {
+        let row_title = world.get::<&Headings>(*cell).unwrap().row;
+        match (visible_rows[j], hide) {
+            (false, true) => hidden.push(row_title),
+            (true, false) if visible_rows.contains(&false) => highlighted.push(row_title),
+            _ => (),
+        }
+    }
+
+    for id in hidden {
+        world.insert_one(id, Hidden).unwrap();
+    }
+    for id in highlighted {
+        world.insert_one(id, Highlight).unwrap();
+    }
+}
 
-        for id in refresh_needed {
-            world.insert_one(id, NeedsRefresh);
+// shades the row and column of the focused cell
+pub fn system_draw_crosshair(world: &World, draw: &Draw, grid_view: Rect) {
+    for (id, (bounds, focus)) in &mut world.query::<(&Bounds, &Focus)>().with::<&Cell>() {
+        if focus.0 {
+            let row = Rect { x: grid_view.x, y: bounds.shape.y };
+            let column = Rect { x: bounds.shape.x, y: grid_view.y };
+            for band in [row, column] {
+                draw.rect().xy(band.xy()).wh(band.wh()).color(rgba(1.0, 1.0, 1.0, 0.08));
+            }
         }
+    }
+}
+
+fn focused_headings(world: &World) -> Option<(Entity, Entity)> {
+    world
+        .query::<(&Headings, &Focus)>()
+        .iter()
+        .find(|(_, (_, focus))| focus.0)
+        .map(|(_, (headings, _))| (headings.row, headings.column))
+}
 
-        refresh_loop_counter += 1;
+// redraws the titles of the focused cell in bold
+pub fn system_draw_hovered_titles(
+    world: &World,
+    draw: &Draw,
+    font: &text::Font,
+    orientation: &Orientation,
+) {
+    let (row, column) = match focused_headings(world) {
+        Some(headings) => headings,
+        None => return,
+    };
+    for id in [row, column] {
+        let mut query = world.query_one::<(&Title, &Bounds, &LabelText)>(id).unwrap();
+        let (title, bounds, label) = match query.get() {
+            Some((title, bounds, label)) if title.orientation == *orientation =>
+                (title, bounds, label),
+            _ => continue,
+        };
+        draw.rect()
+            .xy(bounds.shape.xy())
+            .wh(bounds.shape.wh())
+            .color(BLACK)
+            .stroke_color(PINK)
+            .stroke_weight(line_weight * 1.5);
+
+        let text = draw
+            .text(&title.text)
+            .xy(bounds.shape.xy())
+            .font(font.clone())
+            .color(PINK)
+            .no_line_wrap()
+            .font_size(label.size)
+            .wh(bounds.shape.wh());
+        match title.orientation {
+            Orientation::Horizontal => text.rotate(TAU / 4.0).left_justify(),
+            Orientation::Vertical => text.right_justify(),
+        };
+    }
+}
+
+// source -> destination names, value, state and the board descriptions of the focused cell
+pub fn system_draw_tooltip(world: &World, draw: &Draw, font: &text::Font, screen: Rect) {
+    let mut lines = vec![];
+    let mut anchor = Vec2::ZERO;
+    for (id, (cell, headings, bounds, focus)) in
+        &mut world.query::<(&Cell, &Headings, &Bounds, &Focus)>().without::<&Hidden>()
+    {
+        if !focus.0 {
+            continue;
+        }
+        let name = |id: Entity| world.get::<&Title>(id).unwrap().text.clone();
+        let description = |id: Entity| world.get::<&Description>(id).unwrap().0.clone();
+
+        lines.push(format!("{} -> {}", name(headings.row), name(headings.column)));
+
+        let mut state = format!("value {}", cell.as_string());
+        if is_locked(world, id, headings) {
+            state.push_str(", locked");
+        }
+        if world.entity(id).is_ok_and(|entity| entity.has::<Momentary>()) {
+            state.push_str(", momentary");
+        }
+        lines.push(state);
+
+        for heading in [headings.row, headings.column] {
+            let description = description(heading);
+            if !description.is_empty() {
+                lines.push(format!("{}: {}", name(heading), description));
+            }
+        }
+        anchor = bounds.shape.top_right();
+    }
+    if lines.is_empty() {
+        return;
+    }
+
+    let (char_w, line_h) = (7.0, 16.0);
+    let width = lines.iter().map(|line| line.len()).max().unwrap() as f32 * char_w + 16.0;
+    let height = lines.len() as f32 * line_h + 8.0;
+    let tooltip =
+        Rect::from_x_y_w_h(anchor.x + 12.0 + width / 2.0, anchor.y - height / 2.0, width, height);
+    // keep it on screen
+    let tooltip = tooltip.shift_x((screen.right() - tooltip.right()).min(0.0));
+    let tooltip = tooltip.shift_y((screen.bottom() - tooltip.bottom()).max(0.0));
+
+    draw.rect()
+        .xy(tooltip.xy())
+        .wh(tooltip.wh())
+        .color(BLACK)
+        .stroke_color(WHITE)
+        .stroke_weight(line_weight / 2.0);
+    for (line, shape) in lines.iter().zip(tooltip.pad(4.0).divide_rows(lines.len() as u32)) {
+        draw.text(line)
+            .xy(shape.xy())
+            .wh(shape.pad_left(4.0).wh())
+            .font(font.clone())
+            .color(WHITE)
+            .font_size(12)
+            .no_line_wrap()
+            .left_justify();
     }
 }
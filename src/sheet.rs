@@ -0,0 +1,180 @@
+use std::fmt::Write;
+
+use nannou_core::prelude::*;
+
+use crate::board::Board;
+use crate::patch::Patch;
+use crate::sub_divide::SubdivideExt;
+
+// a printable patch sheet, laid out in millimetres on a landscape a4 page
+const PAGE_SIZE: (f32, f32) = (297.0, 210.0);
+const MARGIN: f32 = 10.0;
+// the patch name and notes above the grid
+const TITLE_HEIGHT: f32 = 18.0;
+// the source names on the left and the destination names on top
+const LABEL_SIZE: f32 = 26.0;
+const LINE_WEIGHT: f32 = 0.1;
+
+// sources are the rows and destinations the columns, like on screen
+pub fn svg(board: &Board, patch: &Patch) -> String {
+    let page = Rect::from_corners(pt2(0.0, 0.0), pt2(PAGE_SIZE.0, PAGE_SIZE.1));
+    let content = page.pad(MARGIN);
+    let title = Rect::from_corners(
+        pt2(content.left(), content.top() - TITLE_HEIGHT),
+        content.top_right(),
+    );
+    let cells = content.pad_top(TITLE_HEIGHT + LABEL_SIZE).pad_left(LABEL_SIZE);
+
+    let rows = cells.divide_rows(board.sources.len().max(1) as u32);
+    let columns = cells.divide_columns(board.destinations.len().max(1) as u32);
+    let text_size = |rect: &Rect| (rect.w().min(rect.h()) * 0.7).min(3.0);
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}mm" height="{h}mm" viewBox="0 0 {w} {h}" font-family="monospace">"#,
+        w = PAGE_SIZE.0,
+        h = PAGE_SIZE.1
+    )
+    .unwrap();
+    writeln!(svg, r#"<rect width="100%" height="100%" fill="white"/>"#).unwrap();
+
+    // name, notes and a legend for the pin types
+    let name = if patch.name.is_empty() { "untitled patch" } else { &patch.name };
+    text(&mut svg, pt2(title.left(), title.top() - 6.0), 6.0, "start", &escape(name));
+    for (i, line) in patch.notes.lines().take(2).enumerate() {
+        let position = pt2(title.left(), title.top() - 11.0 - 4.0 * i as f32);
+        text(&mut svg, position, 3.0, "start", &escape(line));
+    }
+    let legend = pt2(title.right() - 60.0, title.top() - 4.0);
+    circle(&mut svg, legend, 1.2, "black");
+    text(&mut svg, legend + vec2(3.0, -1.0), 3.0, "start", "on");
+    circle(&mut svg, legend + vec2(14.0, 0.0), 1.2, "white");
+    text(&mut svg, legend + vec2(17.0, -1.0), 3.0, "start", "level");
+    square(&mut svg, legend + vec2(34.0, 0.0), 2.4);
+    text(&mut svg, legend + vec2(37.0, -1.0), 3.0, "start", "locked");
+
+    // shade every other row so a row can be followed across the page
+    for (j, (row, label)) in rows.iter().zip(&board.sources).enumerate() {
+        let band = Rect { x: cells.x, y: row.y };
+        if j % 2 == 0 {
+            rect(&mut svg, &band, "#eeeeee");
+        }
+        let position = pt2(cells.left() - 1.0, row.y() - text_size(row) / 3.0);
+        text(&mut svg, position, text_size(row), "end", &escape(&label.name));
+    }
+    for (column, label) in columns.iter().zip(&board.destinations) {
+        let position = pt2(column.x() + text_size(column) / 3.0, cells.top() + 1.0);
+        let (x, y) = to_svg(position);
+        writeln!(
+            svg,
+            r#"<text x="{x:.2}" y="{y:.2}" font-size="{:.2}" transform="rotate(-90 {x:.2} {y:.2})">{}</text>"#,
+            text_size(column),
+            escape(&label.name)
+        )
+        .unwrap();
+    }
+
+    // grid lines
+    for row in &rows {
+        line(&mut svg, pt2(cells.left(), row.bottom()), pt2(cells.right(), row.bottom()));
+    }
+    for column in &columns {
+        line(&mut svg, pt2(column.left(), cells.bottom()), pt2(column.left(), cells.top()));
+    }
+    line(&mut svg, cells.top_left(), cells.top_right());
+    line(&mut svg, cells.bottom_right(), cells.top_right());
+
+    // the pins, by the serial row (destination) and column (source)
+    for (i, column) in columns.iter().enumerate() {
+        for (j, row) in rows.iter().enumerate() {
+            let cell = Rect { x: column.x, y: row.y };
+            let radius = cell.w().min(cell.h()) * 0.35;
+            let value = patch.value(i, j);
+            if value >= 1.0 {
+                circle(&mut svg, cell.xy(), radius, "black");
+            } else if value > 0.0 {
+                circle(&mut svg, cell.xy(), radius, "white");
+                // the same levels as the number keys
+                let level = format!("{:.0}", value * 9.0);
+                let position = cell.xy() - vec2(0.0, radius / 2.0);
+                text(&mut svg, position, radius * 1.4, "middle", &level);
+            }
+            if patch.is_locked(i, j) {
+                square(&mut svg, cell.xy(), radius * 2.4);
+            }
+        }
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+// nannou measures up from the bottom left corner, svg down from the top left
+fn to_svg(point: Point2) -> (f32, f32) { (point.x, PAGE_SIZE.1 - point.y) }
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn text(svg: &mut String, position: Point2, size: f32, anchor: &str, text: &str) {
+    let (x, y) = to_svg(position);
+    writeln!(
+        svg,
+        r#"<text x="{:.2}" y="{:.2}" font-size="{:.2}" text-anchor="{}">{}</text>"#,
+        x, y, size, anchor, text
+    )
+    .unwrap();
+}
+
+fn line(svg: &mut String, start: Point2, end: Point2) {
+    let ((x1, y1), (x2, y2)) = (to_svg(start), to_svg(end));
+    writeln!(
+        svg,
+        r#"<line x1="{:.2}" y1="{:.2}" x2="{:.2}" y2="{:.2}" stroke="black" stroke-width="{}"/>"#,
+        x1, y1, x2, y2, LINE_WEIGHT
+    )
+    .unwrap();
+}
+
+fn rect(svg: &mut String, rect: &Rect, fill: &str) {
+    let (x, y) = to_svg(rect.top_left());
+    writeln!(
+        svg,
+        r#"<rect x="{:.2}" y="{:.2}" width="{:.2}" height="{:.2}" fill="{}"/>"#,
+        x,
+        y,
+        rect.w(),
+        rect.h(),
+        fill
+    )
+    .unwrap();
+}
+
+fn circle(svg: &mut String, centre: Point2, radius: f32, fill: &str) {
+    let (x, y) = to_svg(centre);
+    writeln!(
+        svg,
+        r#"<circle cx="{:.2}" cy="{:.2}" r="{:.2}" fill="{}" stroke="black" stroke-width="{}"/>"#,
+        x,
+        y,
+        radius,
+        fill,
+        LINE_WEIGHT * 2.0
+    )
+    .unwrap();
+}
+
+fn square(svg: &mut String, centre: Point2, size: f32) {
+    let (x, y) = to_svg(centre - vec2(size, -size) / 2.0);
+    writeln!(
+        svg,
+        r#"<rect x="{:.2}" y="{:.2}" width="{:.2}" height="{:.2}" fill="none" stroke="orange" stroke-width="{}"/>"#,
+        x,
+        y,
+        size,
+        size,
+        LINE_WEIGHT * 3.0
+    )
+    .unwrap();
+}
@@ -1,4 +1,4 @@
-use nannou::prelude::*;
+use nannou_core::prelude::*;
 
 pub trait SubdivideExt {
     fn divide_columns(&self, count: u32) -> Vec<Rect>;
@@ -6,6 +6,9 @@ pub trait SubdivideExt {
     fn divide_rows_cols(&self, rows: u32, columns: u32) -> Vec<Vec<Rect>> {
         self.divide_rows(rows).iter().map(|row| row.divide_columns(columns)).collect()
     }
+    // cells sized in proportion to their weights, from the left and from the top
+    fn divide_columns_weighted(&self, weights: &[f32]) -> Vec<Rect>;
+    fn divide_rows_weighted(&self, weights: &[f32]) -> Vec<Rect>;
 }
 impl SubdivideExt for geom::Rect {
     fn divide_rows(&self, subdivisions: u32) -> Vec<Self> {
@@ -57,4 +60,33 @@ impl SubdivideExt for geom::Rect {
             })
             .collect()
     }
+    fn divide_columns_weighted(&self, weights: &[f32]) -> Vec<Self> {
+        let total: f32 = weights.iter().sum();
+        let scale = if total > 0.0 { self.w() / total } else { 0.0 };
+        let mut left = self.left();
+        weights
+            .iter()
+            .map(|weight| {
+                let width = weight * scale;
+                let cell = Rect::from_x_y_w_h(left + width / 2.0, self.y(), width, self.h());
+                left += width;
+                cell
+            })
+            .collect()
+    }
+    fn divide_rows_weighted(&self, weights: &[f32]) -> Vec<Self> {
+        let total: f32 = weights.iter().sum();
+        let scale = if total > 0.0 { self.h() / total } else { 0.0 };
+        //rows index from the top down, like divide_rows
+        let mut top = self.top();
+        weights
+            .iter()
+            .map(|weight| {
+                let height = weight * scale;
+                let cell = Rect::from_x_y_w_h(self.x(), top - height / 2.0, self.w(), height);
+                top -= height;
+                cell
+            })
+            .collect()
+    }
 }
@@ -0,0 +1,56 @@
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::port_scanner::{PortMatcher, PortScanner};
+
+// where the serial protocol goes, the board or standard output for scripting and debugging
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transport {
+    Serial,
+    Stdout,
+}
+
+impl FromStr for Transport {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "serial" => Ok(Transport::Serial),
+            "stdout" => Ok(Transport::Stdout),
+            _ => Err(format!("unknown transport {}, use serial or stdout", s)),
+        }
+    }
+}
+
+impl Transport {
+    // without a port name the first port the matcher picks is used
+    pub fn open(
+        &self,
+        port_name: Option<&str>,
+        matcher: &PortMatcher,
+        baudrate: u32,
+    ) -> io::Result<Box<dyn Write + Send>> {
+        match self {
+            Transport::Stdout => Ok(Box::new(io::stdout())),
+            Transport::Serial => {
+                let port_name = match port_name {
+                    Some(port_name) => port_name.to_owned(),
+                    None => find_port(matcher)
+                        .ok_or(io::Error::new(io::ErrorKind::NotFound, "no matching port found"))?,
+                };
+                let port = serialport::new(port_name, baudrate)
+                    .timeout(Duration::from_millis(100))
+                    .open()
+                    .map_err(io::Error::from)?;
+                Ok(Box::new(port))
+            }
+        }
+    }
+}
+
+pub fn find_port(matcher: &PortMatcher) -> Option<String> {
+    let mut scanner = PortScanner::new(matcher.clone(), Duration::ZERO);
+    let changes = scanner.poll()?;
+    scanner.find_match(&changes.added)
+}
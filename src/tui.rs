@@ -0,0 +1,312 @@
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Color, Print, SetAttribute, SetForegroundColor};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{cursor, execute, queue};
+
+use crate::cli::Options;
+use crate::engine::Engine;
+use crate::patch::Patch;
+use crate::transport::Transport;
+
+// every destination column is this many characters wide
+const CELL_WIDTH: u16 = 3;
+const NAME_WIDTH: u16 = 16;
+// the destination names run down the header, long ones keep their last letters
+const HEADER_LINES: u16 = 10;
+const STATUS_LINES: u16 = 2;
+
+const KEYS: &str =
+    "arrows/hjkl move  space toggle  0-9 level  s save  o load  r resend  c connect  q quit";
+
+// sources are the rows and destinations the columns, like in the window
+struct Tui {
+    engine:     Engine,
+    options:    Options,
+    patch_path: PathBuf,
+    // (source, destination)
+    cursor:     (usize, usize),
+    scroll:     (usize, usize),
+    connected:  bool,
+    message:    String,
+}
+
+// patches the matrix from a terminal, over ssh on the install box for example
+pub fn run(options: &Options) -> io::Result<bool> {
+    if options.transport == Transport::Stdout {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the terminal ui needs the serial transport, stdout is the screen",
+        ));
+    }
+    let board = options.load_board()?;
+    let mut tui = Tui {
+        engine:     Engine::new(board, Box::new(io::sink())),
+        options:    options.clone(),
+        patch_path: options.patch_path(),
+        cursor:     (0, 0),
+        scroll:     (0, 0),
+        connected:  false,
+        message:    String::new(),
+    };
+    tui.connect();
+    if let Some(path) = &options.patch {
+        let patch = Patch::load(path)?;
+        let result = tui.engine.load(&patch);
+        tui.result(result.map(|_| format!("loaded {}", path.display())));
+    }
+
+    let mut out = io::stdout();
+    terminal::enable_raw_mode()?;
+    execute!(out, EnterAlternateScreen, cursor::Hide)?;
+    let result = tui.run(&mut out);
+    execute!(out, LeaveAlternateScreen, cursor::Show)?;
+    terminal::disable_raw_mode()?;
+    result.map(|_| true)
+}
+
+impl Tui {
+    fn run(&mut self, out: &mut impl Write) -> io::Result<()> {
+        loop {
+            self.draw(out)?;
+            if !event::poll(Duration::from_millis(250))? {
+                continue;
+            }
+            match event::read()? {
+                Event::Key(key) if key.kind != KeyEventKind::Release && !self.key_pressed(key) =>
+                    return Ok(()),
+                _ => (),
+            }
+        }
+    }
+
+    // false to quit
+    fn key_pressed(&mut self, key: KeyEvent) -> bool {
+        let (source, destination) = self.cursor;
+        let (row, column) = (destination, source);
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return false,
+            KeyCode::Up | KeyCode::Char('k') => self.move_cursor(0, -1),
+            KeyCode::Down | KeyCode::Char('j') => self.move_cursor(0, 1),
+            KeyCode::Left | KeyCode::Char('h') => self.move_cursor(-1, 0),
+            KeyCode::Right | KeyCode::Char('l') => self.move_cursor(1, 0),
+            KeyCode::PageUp => self.move_cursor(0, -(self.visible().1 as i32)),
+            KeyCode::PageDown => self.move_cursor(0, self.visible().1 as i32),
+            KeyCode::Char(' ') | KeyCode::Enter => {
+                let value = if self.engine.matrix().value(row, column) != 0.0 { 0.0 } else { 1.0 };
+                self.set(row, column, value);
+            }
+            KeyCode::Char(c) if c.is_ascii_digit() => {
+                // same levels as the window, 9 is fully on
+                let value = c.to_digit(10).unwrap() as f32 / 9.0;
+                self.set(row, column, value);
+            }
+            KeyCode::Char('s') => {
+                let path = self.patch_path.clone();
+                let result = self.engine.matrix().patch().save(&path);
+                self.result(result.map(|_| format!("saved {}", path.display())));
+            }
+            KeyCode::Char('o') => {
+                let path = self.patch_path.clone();
+                let result = Patch::load(&path).and_then(|patch| self.engine.load(&patch));
+                self.result(result.map(|_| format!("loaded {}", path.display())));
+            }
+            KeyCode::Char('r') => {
+                let result = self.engine.send_all();
+                self.result(result.map(|_| "sent the whole matrix".to_string()));
+            }
+            KeyCode::Char('c') => self.connect(),
+            _ => (),
+        }
+        true
+    }
+
+    fn set(&mut self, row: usize, column: usize, value: f32) {
+        if self.engine.matrix().is_locked(row, column) {
+            self.message = "locked".to_string();
+            return;
+        }
+        let result = self.engine.set(row, column, value);
+        self.result(result.map(|_| String::new()));
+    }
+
+    // a failed write means the board is gone
+    fn result(&mut self, result: io::Result<String>) {
+        match result {
+            Ok(message) => self.message = message,
+            Err(e) => {
+                self.message = format!("Failed to write to port: {}", e);
+                self.connected = false;
+                self.engine.set_output(Box::new(io::sink()));
+            }
+        }
+    }
+
+    fn connect(&mut self) {
+        let options = &self.options;
+        match options.transport.open(
+            options.port.as_deref(),
+            &options.port_matcher(),
+            options.baudrate,
+        ) {
+            Ok(output) => {
+                self.engine.set_output(output);
+                self.connected = true;
+                let result = self.engine.send_all();
+                self.result(result.map(|_| "connected".to_string()));
+            }
+            Err(e) => {
+                self.connected = false;
+                self.message = format!("Failed to open port: {}", e);
+            }
+        }
+    }
+
+    fn move_cursor(&mut self, x: i32, y: i32) {
+        let board = self.engine.matrix().board();
+        let clamp = |i: usize, by: i32, len: usize| {
+            (i as i32 + by).clamp(0, len.saturating_sub(1) as i32) as usize
+        };
+        self.cursor = (
+            clamp(self.cursor.0, y, board.sources.len()),
+            clamp(self.cursor.1, x, board.destinations.len()),
+        );
+    }
+
+    // (destinations, sources) that fit on screen
+    fn visible(&self) -> (usize, usize) {
+        let (width, height) = terminal::size().unwrap_or((80, 24));
+        (
+            (width.saturating_sub(NAME_WIDTH + 1) / CELL_WIDTH).max(1) as usize,
+            height.saturating_sub(self.header_lines() + STATUS_LINES).max(1) as usize,
+        )
+    }
+
+    // as tall as the longest destination name, up to `HEADER_LINES`
+    fn header_lines(&self) -> u16 {
+        let destinations = &self.engine.matrix().board().destinations;
+        let longest = destinations.iter().map(|label| label.name.chars().count()).max();
+        longest.unwrap_or(1).clamp(1, HEADER_LINES as usize) as u16
+    }
+
+    // keeps the cursor on screen
+    fn scroll_to_cursor(&mut self) {
+        let (columns, rows) = self.visible();
+        let follow = |scroll: usize, cursor: usize, visible: usize| {
+            scroll.min(cursor).max((cursor + 1).saturating_sub(visible))
+        };
+        self.scroll = (
+            follow(self.scroll.0, self.cursor.0, rows),
+            follow(self.scroll.1, self.cursor.1, columns),
+        );
+    }
+
+    fn draw(&mut self, out: &mut impl Write) -> io::Result<()> {
+        self.scroll_to_cursor();
+        let (columns, rows) = self.visible();
+        let matrix = self.engine.matrix();
+        let board = matrix.board();
+        let destinations = self.scroll.1..board.destinations.len().min(self.scroll.1 + columns);
+        let sources = self.scroll.0..board.sources.len().min(self.scroll.0 + rows);
+
+        // every line is cleared behind its text instead of clearing the screen, which flickers
+        // pinned header with the destination names read downwards, ending right above the grid
+        let header_lines = self.header_lines();
+        let names: Vec<Vec<char>> =
+            board.destinations.iter().map(|label| label.name.chars().collect()).collect();
+        for line in 0..header_lines {
+            queue!(
+                out,
+                cursor::MoveTo(0, line),
+                Print(format!("{:width$} ", "", width = NAME_WIDTH as usize))
+            )?;
+            // letters counted from the end of the name
+            let from_end = (header_lines - line) as usize;
+            for destination in destinations.clone() {
+                let name = &names[destination];
+                let letter = name.len().checked_sub(from_end).map_or(' ', |i| name[i]);
+                let attribute =
+                    if destination == self.cursor.1 { Attribute::Bold } else { Attribute::Dim };
+                queue!(
+                    out,
+                    SetAttribute(attribute),
+                    Print(format!(" {} ", letter)),
+                    SetAttribute(Attribute::Reset)
+                )?;
+            }
+            queue!(out, Clear(ClearType::UntilNewLine))?;
+        }
+
+        let grid_lines = sources.len() as u16;
+        for (line, source) in sources.enumerate() {
+            let name = &board.sources[source].name;
+            let attribute =
+                if source == self.cursor.0 { Attribute::Bold } else { Attribute::Reset };
+            queue!(
+                out,
+                cursor::MoveTo(0, header_lines + line as u16),
+                SetAttribute(attribute),
+                Print(format!("{:>width$.width$} ", name, width = NAME_WIDTH as usize)),
+                SetAttribute(Attribute::Reset),
+            )?;
+            for destination in destinations.clone() {
+                let (row, column) = (destination, source);
+                let value = matrix.value(row, column);
+                let symbol = match value {
+                    v if v >= 1.0 => " # ",
+                    v if v > 0.0 => " + ",
+                    _ => " . ",
+                };
+                let colour =
+                    if matrix.is_locked(row, column) { Color::DarkYellow } else { Color::Reset };
+                if (source, destination) == self.cursor {
+                    queue!(out, SetAttribute(Attribute::Reverse))?;
+                }
+                queue!(
+                    out,
+                    SetForegroundColor(colour),
+                    Print(symbol),
+                    SetAttribute(Attribute::Reset)
+                )?;
+            }
+            queue!(out, Clear(ClearType::UntilNewLine))?;
+        }
+
+        // whatever a taller grid left between the grid and the status
+        let (_, height) = terminal::size().unwrap_or((80, 24));
+        for line in header_lines + grid_lines..height.saturating_sub(STATUS_LINES) {
+            queue!(out, cursor::MoveTo(0, line), Clear(ClearType::UntilNewLine))?;
+        }
+
+        // status: the focused connection, the port and the last message
+        let (source, destination) = self.cursor;
+        let port = match (&self.options.port, self.connected) {
+            (Some(port), true) => format!("connected to {}", port),
+            (None, true) => "connected".to_string(),
+            (_, false) => "not connected".to_string(),
+        };
+        queue!(
+            out,
+            cursor::MoveTo(0, height.saturating_sub(STATUS_LINES)),
+            Print(format!(
+                "{} -> {} {:.2} | {} | {}",
+                board.sources[source].name,
+                board.destinations[destination].name,
+                matrix.value(destination, source),
+                port,
+                self.message
+            )),
+            Clear(ClearType::UntilNewLine),
+            cursor::MoveTo(0, height.saturating_sub(1)),
+            SetAttribute(Attribute::Dim),
+            Print(KEYS),
+            SetAttribute(Attribute::Reset),
+            Clear(ClearType::UntilNewLine),
+        )?;
+        out.flush()
+    }
+}